@@ -0,0 +1,89 @@
+use anyhow::{Context, Result, anyhow, bail};
+use nix::{
+    errno::Errno,
+    sched::{CpuSet, sched_setaffinity},
+    unistd::Pid,
+};
+
+use crate::processes::get_thread_ids;
+
+///Parse a cpu list as the kernel writes it, e.g. "0-3,8,10-11"
+pub fn parse_cpu_list(list: &str) -> Result<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in list.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .with_context(|| format!("'{n}' is not a cpu number"))
+        };
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse(first)?, parse(last)?);
+                if first > last {
+                    bail!("Cpu range '{part}' runs backwards");
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(parse(part)?),
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+///Parse what the user typed in the affinity prompt: a cpu list, optionally followed
+///by "main" to only pin the main thread. Returns the cpus and whether to pin every thread.
+pub fn parse_affinity_input(input: &str) -> Result<(Vec<usize>, bool)> {
+    let input = input.trim();
+    let (list, all_threads) = match input.strip_suffix("main") {
+        Some(list) => (list, false),
+        None => (input, true),
+    };
+    let cpus = parse_cpu_list(list)?;
+    if cpus.is_empty() {
+        bail!("A process needs at least one cpu");
+    }
+    Ok((cpus, all_threads))
+}
+
+///Pin a process, or every one of its threads, to `cpus`. Returns how many threads were pinned.
+pub fn set_affinity(pid: u64, cpus: &[usize], all_threads: bool) -> Result<usize> {
+    let mut set = CpuSet::new();
+    for &cpu in cpus {
+        set.set(cpu)
+            .map_err(|_| anyhow!("Cpu {cpu} is beyond what can be pinned"))?;
+    }
+    let tids = if all_threads {
+        get_thread_ids(pid)?
+    } else {
+        vec![pid]
+    };
+    let mut pinned = 0;
+    for tid in tids {
+        match sched_setaffinity(Pid::from_raw(tid as i32), &set) {
+            Ok(()) => pinned += 1,
+            //the thread exited while we were at it
+            Err(Errno::ESRCH) => continue,
+            Err(e) => return Err(anyhow!("Cannot pin thread {tid}: {e}")),
+        }
+    }
+    Ok(pinned)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_cpu_list() -> Result<()> {
+        assert_eq!(parse_cpu_list("0-3,8,10-11")?, vec![0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(parse_cpu_list("5, 2,2")?, vec![2, 5]);
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("one").is_err());
+        assert_eq!(parse_affinity_input("0-1 main")?, (vec![0, 1], false));
+        assert_eq!(parse_affinity_input("2")?, (vec![2], true));
+        assert!(parse_affinity_input("main").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    io::{BufReader, ErrorKind, Write},
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::{
+    app::{apply_to_targets, describe_done},
+    collector::Collector,
+    nice::set_nice,
+    protocol::{
+        AgentMessage, ClientMessage, Connection, Endpoint, HELLO_TIMEOUT, MAX_ACTION_LEN,
+        MAX_HELLO_LEN, PROTOCOL_VERSION, read_message, snapshot_line, write_message,
+    },
+    signals::{parse_signal, send_signal},
+    system::hostname,
+};
+
+///Clients served at once, each costs a thread and a copy of every snapshot
+const MAX_CLIENTS: usize = 32;
+
+///Counts a client in for as long as it is kept
+struct ClientSlot(Arc<AtomicUsize>);
+
+impl ClientSlot {
+    ///A slot, unless `MAX_CLIENTS` are connected already
+    fn take(clients: &Arc<AtomicUsize>) -> Option<Self> {
+        clients
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < MAX_CLIENTS).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Self(clients.clone()))
+    }
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+///Stream snapshots to every client that connects to `listen`, until killed.
+///With a token, clients have to present it and may send signals and renice.
+pub fn run_agent(listen: &Endpoint, interval: Duration, token: Option<String>) -> Result<()> {
+    let listener = listen.listen()?;
+    let collector = Collector::start(interval);
+    let token = Arc::new(token);
+    let clients = Arc::new(AtomicUsize::new(0));
+    println!(
+        "Agent on {listen}, {}",
+        match token.is_some() {
+            true => "signals and renice allowed with the token",
+            false => "read only, start with a token to allow signals and renice",
+        }
+    );
+    loop {
+        let mut connection = match listener.accept() {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Accept failed: {e}");
+                continue;
+            }
+        };
+        let Some(slot) = ClientSlot::take(&clients) else {
+            let refused = AgentMessage::Refused(format!("Already serving {MAX_CLIENTS} clients"));
+            let _ = write_message(&mut connection, &refused);
+            continue;
+        };
+        let collector = collector.clone();
+        let token = token.clone();
+        std::thread::spawn(move || {
+            let _slot = slot;
+            if let Err(e) = serve_client(connection, &collector, token.as_deref()) {
+                eprintln!("Client dropped: {e}");
+            }
+        });
+    }
+}
+
+///Same time whatever the first wrong byte, so a token can't be guessed a byte at a time
+fn tokens_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+///Why a client may not connect, if it may not
+fn check_hello(hello: Option<ClientMessage>, token: Option<&str>) -> Result<()> {
+    match hello {
+        Some(ClientMessage::Hello { version, .. }) if version != PROTOCOL_VERSION => bail!(
+            "Agent speaks protocol version {PROTOCOL_VERSION}, not {version}, upgrade the older one"
+        ),
+        Some(ClientMessage::Hello { token: given, .. }) => match token {
+            Some(expected) if !tokens_match(expected, given.as_deref().unwrap_or_default()) => {
+                bail!("Wrong token")
+            }
+            _ => Ok(()),
+        },
+        Some(_) => bail!("Expected a hello first"),
+        None => bail!("Hung up before saying hello"),
+    }
+}
+
+///Carry out what a client asked for, returning what to show in its status bar
+fn perform(message: ClientMessage, allowed: bool) -> Result<String> {
+    if !allowed {
+        bail!("This agent is read only, start it with a token to allow signals and renice");
+    }
+    match message {
+        ClientMessage::Hello { .. } => Err(anyhow!("Already said hello")),
+        //the client saw the targets a refresh or more ago, their pids may have been reused since
+        ClientMessage::Signal { targets, signal } => {
+            let signal = parse_signal(&signal)?;
+            apply_to_targets(&targets, |pid| send_signal(pid, signal)).map(|done| {
+                format!(
+                    "Sent {} to {}",
+                    signal.as_str(),
+                    describe_done(&targets, done)
+                )
+            })
+        }
+        ClientMessage::Renice { targets, nice } => {
+            apply_to_targets(&targets, |pid| set_nice(pid, nice).map(|_| ()))
+                .map(|done| format!("Reniced {} to {nice}", describe_done(&targets, done)))
+        }
+    }
+}
+
+fn lock(writer: &Mutex<Connection>) -> Result<MutexGuard<'_, Connection>> {
+    writer
+        .lock()
+        .map_err(|_| anyhow!("Connection lock poisoned"))
+}
+
+fn serve_client(connection: Connection, collector: &Collector, token: Option<&str>) -> Result<()> {
+    let mut reader = BufReader::new(connection.try_clone()?);
+    let writer = Arc::new(Mutex::new(connection));
+
+    //a client that connects and says nothing would hold its slot forever
+    lock(&writer)?.set_read_timeout(Some(HELLO_TIMEOUT))?;
+    let hello = read_message(&mut reader, MAX_HELLO_LEN).map_err(|e| {
+        match e.downcast_ref::<std::io::Error>().map(|e| e.kind()) {
+            Some(ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                anyhow!("No hello within {}s", HELLO_TIMEOUT.as_secs())
+            }
+            _ => e,
+        }
+    })?;
+    if let Err(e) = check_hello(hello, token) {
+        write_message(&mut *lock(&writer)?, &AgentMessage::Refused(e.to_string()))?;
+        return Err(e);
+    }
+    //actions come whenever the user gets round to them
+    lock(&writer)?.set_read_timeout(None)?;
+    write_message(
+        &mut *lock(&writer)?,
+        &AgentMessage::Welcome {
+            version: PROTOCOL_VERSION,
+            hostname: hostname(),
+            actions: token.is_some(),
+        },
+    )?;
+
+    //snapshots go out from a thread of their own, actions are answered from this one
+    let snapshots = collector.subscribe()?;
+    let snapshot_writer = writer.clone();
+    std::thread::spawn(move || -> Result<()> {
+        for json in snapshots {
+            let mut connection = lock(&snapshot_writer)?;
+            connection.write_all(snapshot_line(&json).as_bytes())?;
+            connection.flush()?;
+        }
+        Ok(())
+    });
+
+    let result = loop {
+        match read_message(&mut reader, MAX_ACTION_LEN) {
+            Ok(Some(message)) => {
+                let reply = match perform(message, token.is_some()) {
+                    Ok(done) => AgentMessage::ActionDone(done),
+                    Err(e) => AgentMessage::ActionFailed(e.to_string()),
+                };
+                if let Err(e) = write_message(&mut *lock(&writer)?, &reply) {
+                    break Err(e);
+                }
+            }
+            Ok(None) => break Ok(()),
+            Err(e) => break Err(e),
+        }
+    };
+    //the snapshot thread stops at its next write
+    lock(&writer)?.shutdown();
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::net::UnixStream;
+
+    use super::*;
+    use crate::{
+        model::{HostClock, ProcessInfo, Target},
+        processes::read_start_time,
+        protocol::MAX_MESSAGE_LEN,
+        system::{Snapshot, SystemStats},
+    };
+
+    fn hello(token: Option<&str>) -> Option<ClientMessage> {
+        Some(ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            token: token.map(String::from),
+        })
+    }
+
+    #[test]
+    pub fn test_check_hello() {
+        assert!(check_hello(hello(None), None).is_ok());
+        assert!(check_hello(hello(Some("anything")), None).is_ok());
+        assert!(check_hello(hello(Some("secret")), Some("secret")).is_ok());
+        assert!(check_hello(hello(Some("secreT")), Some("secret")).is_err());
+        assert!(check_hello(hello(None), Some("secret")).is_err());
+        let old = Some(ClientMessage::Hello {
+            version: 0,
+            token: None,
+        });
+        assert!(check_hello(old, None).is_err());
+        assert!(check_hello(None, None).is_err());
+    }
+
+    #[test]
+    pub fn test_client_slots() {
+        let clients = Arc::new(AtomicUsize::new(0));
+        let mut slots: Vec<ClientSlot> = (0..MAX_CLIENTS)
+            .map_while(|_| ClientSlot::take(&clients))
+            .collect();
+        assert_eq!(slots.len(), MAX_CLIENTS);
+        assert!(ClientSlot::take(&clients).is_none());
+        slots.pop();
+        assert!(ClientSlot::take(&clients).is_some());
+    }
+
+    #[test]
+    pub fn test_serve_client() -> Result<()> {
+        let collector = Arc::new(Collector::default());
+        let mut process = ProcessInfo::new();
+        process.pid = 42;
+        collector.publish(Snapshot {
+            timestamp: 0,
+            clock: HostClock {
+                boot_time: 1000,
+                clock_ticks: 100.0,
+            },
+            system: SystemStats::default(),
+            processes: vec![process],
+        })?;
+        let (agent_end, client_end) = UnixStream::pair()?;
+        let agent_collector = collector.clone();
+        let agent = std::thread::spawn(move || {
+            serve_client(
+                Connection::Unix(agent_end),
+                &agent_collector,
+                Some("secret"),
+            )
+        });
+
+        let mut client = Connection::Unix(client_end);
+        let mut reader = BufReader::new(client.try_clone()?);
+        let mut next = || read_message::<AgentMessage>(&mut reader, MAX_MESSAGE_LEN);
+        write_message(&mut client, &hello(Some("secret")).unwrap())?;
+        assert!(matches!(
+            next()?,
+            Some(AgentMessage::Welcome { actions: true, .. })
+        ));
+        match next()? {
+            Some(AgentMessage::Snapshot(snapshot)) => assert_eq!(snapshot.processes[0].pid, 42),
+            other => panic!("expected a snapshot, got {other:?}"),
+        }
+        let pid = std::process::id() as u64;
+        let target = Target {
+            pid,
+            start_time: read_start_time(pid)?,
+        };
+        let signal = |targets: Vec<Target>| ClientMessage::Signal {
+            targets,
+            signal: "CONT".to_string(),
+        };
+        write_message(&mut client, &signal(vec![target]))?;
+        assert!(matches!(next()?, Some(AgentMessage::ActionDone(_))));
+        //a pid that went to another process is left alone
+        let reused = Target {
+            start_time: target.start_time + 1,
+            ..target
+        };
+        write_message(&mut client, &signal(vec![reused]))?;
+        assert!(matches!(next()?, Some(AgentMessage::ActionFailed(_))));
+        //everything tagged on a busy host is far more than a hello
+        write_message(&mut client, &signal(vec![target; 500]))?;
+        match next()? {
+            Some(AgentMessage::ActionDone(done)) => {
+                assert_eq!(done, "Sent SIGCONT to 500 processes")
+            }
+            other => panic!("expected the action done, got {other:?}"),
+        }
+        client.shutdown();
+        agent.join().unwrap()?;
+        Ok(())
+    }
+}
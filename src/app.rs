@@ -1,15 +1,166 @@
-use std::{cmp::Ordering, time::Instant};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::{Duration, Instant},
+};
 
-use anyhow::Result;
-use crossterm::event::KeyCode;
+use anyhow::{Result, anyhow, bail};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use nix::sys::signal::Signal;
 use ratatui::widgets::TableState;
 use users::UsersCache;
 
 use crate::{
-    model::{ProcessInfo, SortBy},
-    processes::{get_process_info, update_cpu_percent},
+    affinity::{parse_affinity_input, set_affinity},
+    commands::{Command, Palette},
+    detail::{DetailTab, ProcessDetail},
+    disk::DiskMonitor,
+    events::{CommandCpu, EventLog, LifecycleEvent, LifecycleKind, diff_snapshots},
+    groups::{group_processes, sort_groups},
+    limits::{format_limit_value, parse_limit_input, set_limit},
+    model::{GroupBy, ProcessGroup, ProcessInfo, ProcessState, SortBy, Target},
+    net::NetworkMonitor,
+    nice::{parse_nice, set_nice},
+    oom::{parse_oom_score_adj, set_oom_score_adj},
+    overview::{HostSort, HostSummary, HostView, host_label, summarize},
+    pressure::{Pressure, PressureMonitor, process_cgroup, read_cgroup_pressure},
+    proc_connector::ProcConnector,
+    processes::{check_target, get_process_info, update_activity_rates, update_cpu_percent},
+    prompt::{Prompt, PromptAction},
+    protocol::ClientMessage,
+    remote::Remote,
+    rules::{Field, Predicate, RuleEngine},
+    security::read_lsm_label,
+    sensors::{Sensor, read_sensors},
+    signals::{parse_signal, send_signal},
 };
 
+///How long a notice stays in the status bar
+const NOTICE_DURATION: Duration = Duration::from_secs(5);
+
+///Run `action` on every item, carrying on past failures. Returns how many succeeded,
+///or an error with the first failure if any did fail.
+pub fn apply_to_all<T: Copy>(
+    items: &[T],
+    mut action: impl FnMut(T) -> Result<()>,
+) -> Result<usize> {
+    let mut done = 0;
+    let mut errors = Vec::new();
+    for &item in items {
+        match action(item) {
+            Ok(()) => done += 1,
+            Err(e) => errors.push(e),
+        }
+    }
+    match errors.into_iter().next() {
+        None => Ok(done),
+        Some(e) if items.len() == 1 => Err(e),
+        Some(e) => Err(anyhow!(
+            "{} of {} failed, first: {e}",
+            items.len() - done,
+            items.len()
+        )),
+    }
+}
+
+///Run `action` on the pid of every target that is still the process it was picked as.
+///Targets that exited since, or whose pid now belongs to another process, count as failures.
+pub fn apply_to_targets(
+    targets: &[Target],
+    mut action: impl FnMut(u64) -> Result<()>,
+) -> Result<usize> {
+    apply_to_all(targets, |target| {
+        check_target(&target)?;
+        action(target.pid)
+    })
+}
+
+///Order of two processes under a sort, ascending
+fn compare_processes(a: &ProcessInfo, b: &ProcessInfo, sort: &SortBy) -> Ordering {
+    match sort {
+        SortBy::Cpu => a
+            .cpu_percent
+            .partial_cmp(&b.cpu_percent)
+            .unwrap_or(Ordering::Equal),
+        SortBy::Memory => a.memory_kb.cmp(&b.memory_kb),
+        SortBy::Pid => a.pid.cmp(&b.pid),
+        SortBy::Name => a.name.cmp(&b.name),
+        SortBy::Command => a.command.cmp(&b.command),
+        SortBy::Start => a.start_time.cmp(&b.start_time),
+        //youngest first, the most recently started has the largest start time
+        SortBy::Age => Reverse(a.start_time).cmp(&Reverse(b.start_time)),
+        SortBy::Time => a.cpu_time_total.cmp(&b.cpu_time_total),
+        SortBy::OomScore => a.oom_score.cmp(&b.oom_score),
+        SortBy::OomScoreAdj => a.oom_score_adj.cmp(&b.oom_score_adj),
+        SortBy::MinorFaults
+        | SortBy::MajorFaults
+        | SortBy::VoluntarySwitches
+        | SortBy::InvoluntarySwitches => sort
+            .activity_rate(&a.activity_rates)
+            .partial_cmp(&sort.activity_rate(&b.activity_rates))
+            .unwrap_or(Ordering::Equal),
+    }
+}
+
+///"1234" for a single target, "12 processes" for more
+pub fn describe_done(targets: &[Target], done: usize) -> String {
+    match targets {
+        [target] => target.pid.to_string(),
+        _ => format!("{done} processes"),
+    }
+}
+
+///A row of the process table, indices point into `App::processes` and `App::groups`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TableRow {
+    Process(usize),
+    Group(usize),
+    ///A process shown underneath its expanded group
+    Member(usize),
+    ///A process that exited a moment ago, index into `App::ghosts`
+    Ghost(usize),
+}
+
+///An exited process, still shown for a little while so it doesn't silently vanish
+#[derive(Debug, Clone)]
+pub struct Ghost {
+    pub process: ProcessInfo,
+    pub exited_at: Instant,
+}
+
+///What a table row shows, stable across refreshes unlike its index
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RowKey {
+    ///Pids get reused, the start time tells the processes apart,
+    ///and with several hosts in one table the host does
+    Process {
+        host: Option<String>,
+        pid: u64,
+        start_time: u64,
+    },
+    Group(String),
+}
+
+impl RowKey {
+    pub fn of(process: &ProcessInfo) -> Self {
+        RowKey::Process {
+            host: process.host.clone(),
+            pid: process.pid,
+            start_time: process.start_time,
+        }
+    }
+}
+
+///A process the selection stays on wherever it moves to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Followed {
+    pub host: Option<String>,
+    pub pid: u64,
+    pub start_time: u64,
+    pub name: String,
+}
+
 pub struct App {
     pub processes: Vec<ProcessInfo>,
     pub sort_by: Option<SortBy>,
@@ -21,6 +172,75 @@ pub struct App {
     pub should_quit: bool,
     ///This will place the selection at the top when sorting changes
     pub should_go_to_top: bool,
+    ///When set, processes are collapsed into groups
+    pub group_by: Option<GroupBy>,
+    pub groups: Vec<ProcessGroup>,
+    ///Keys of the groups whose members are shown
+    pub expanded_groups: HashSet<String>,
+    ///Processes that started or exited since we began watching
+    pub event_log: EventLog,
+    pub show_events: bool,
+    ///Kernel process events, when we were allowed to subscribe
+    pub proc_connector: Option<ProcConnector>,
+    ///Agents we watch, in the order they were given
+    pub remotes: Vec<Remote>,
+    ///This host's processes, one agent's, all of them, or none with the host overview
+    pub host_view: HostView,
+    pub host_sort: HostSort,
+    ///Selection in the host overview, the row after the hosts is all of them merged
+    pub host_state: TableState,
+    ///The table has to be rebuilt for a new view, without waiting for the agents
+    pub view_changed: bool,
+    ///Cpu used by exited processes, per short command
+    pub command_cpu: HashMap<String, CommandCpu>,
+    ///Message shown in the status bar until it expires
+    pub notice: Option<(String, Instant)>,
+    ///Alert rules checked on every refresh
+    pub rules: Option<RuleEngine>,
+    pub network: NetworkMonitor,
+    pub show_network: bool,
+    pub disks: DiskMonitor,
+    pub show_disks: bool,
+    pub pressure: PressureMonitor,
+    ///Cgroup of the selected process and its cpu, memory and io pressure
+    pub selected_cgroup_pressure: Option<(String, [Pressure; 3])>,
+    ///Only read while the sensors panel is shown
+    pub sensors: Vec<Sensor>,
+    pub show_sensors: bool,
+    ///Detail view of a single process, shown instead of the table while open
+    pub detail: Option<ProcessDetail>,
+    ///Text input that takes all keys while open
+    pub prompt: Option<Prompt>,
+    ///Show the security flags column
+    pub show_security: bool,
+    ///Read LSM labels even when nothing here looks at them, for snapshots served to clients
+    pub read_labels: bool,
+    ///Show the page fault and context switch rate columns
+    pub show_activity: bool,
+    ///Show the OOM score columns
+    pub show_oom: bool,
+    ///Kept selected through re-sorts, with a notice when it exits
+    pub following: Option<Followed>,
+    ///Processes younger than this are highlighted, zero turns it off
+    pub new_highlight: Duration,
+    ///How long exited processes stay in the table, zero turns it off
+    pub ghost_duration: Duration,
+    pub ghosts: Vec<Ghost>,
+    ///Processes bulk actions apply to instead of the selection
+    pub tagged: HashSet<RowKey>,
+    ///Command palette, takes all keys while open
+    pub palette: Option<Palette>,
+    ///Keys of the current view, shown over it
+    pub show_help: bool,
+    pub help_scroll: u16,
+    ///Rows visible in the process table, what PageUp and PageDown move by
+    pub page_size: usize,
+    ///Digits typed before a motion, e.g. the 5 in "5j"
+    pub pending_count: Option<usize>,
+    ///The first g of "gg" was typed
+    pub pending_g: bool,
+    ///Only processes matching the predicate are shown, kept with the text it was parsed from
+    pub filter: Option<(String, Predicate)>,
 }
 
 impl App {
@@ -39,32 +259,733 @@ impl App {
             },
             should_quit: false,
             should_go_to_top: false,
+            group_by: None,
+            groups: Vec::new(),
+            expanded_groups: HashSet::new(),
+            event_log: EventLog::default(),
+            show_events: false,
+            proc_connector: None,
+            remotes: Vec::new(),
+            host_view: HostView::Local,
+            host_sort: HostSort::default(),
+            host_state: {
+                let mut state = TableState::default();
+                state.select(Some(0));
+                state
+            },
+            view_changed: false,
+            command_cpu: HashMap::new(),
+            notice: None,
+            rules: None,
+            network: NetworkMonitor::default(),
+            show_network: false,
+            disks: DiskMonitor::default(),
+            show_disks: false,
+            pressure: PressureMonitor::default(),
+            selected_cgroup_pressure: None,
+            sensors: Vec::new(),
+            show_sensors: false,
+            detail: None,
+            prompt: None,
+            show_security: false,
+            read_labels: false,
+            show_activity: false,
+            show_oom: false,
+            following: None,
+            new_highlight: Duration::from_secs(10),
+            ghost_duration: Duration::from_secs(5),
+            ghosts: Vec::new(),
+            tagged: HashSet::new(),
+            palette: None,
+            show_help: false,
+            help_scroll: 0,
+            page_size: 10,
+            pending_count: None,
+            pending_g: false,
+            filter: None,
+        }
+    }
+
+    ///Show a message in the status bar for a little while
+    pub fn notify(&mut self, message: impl Into<String>) {
+        self.notice = Some((message.into(), Instant::now()));
+    }
+
+    pub fn current_notice(&self) -> Option<&str> {
+        self.notice
+            .as_ref()
+            .filter(|(_, shown_at)| shown_at.elapsed() < NOTICE_DURATION)
+            .map(|(message, _)| message.as_str())
+    }
+
+    ///Switch lifecycle tracking to kernel events, staying with /proc polling if we can't
+    pub fn start_proc_connector(&mut self) {
+        match ProcConnector::start() {
+            Ok(connector) => self.proc_connector = Some(connector),
+            Err(e) => self.notify(format!("Proc connector unavailable ({e}), polling /proc")),
+        }
+    }
+
+    fn record_event(&mut self, mut event: LifecycleEvent) {
+        if event.kind == LifecycleKind::Exited {
+            //fill in what the kernel doesn't tell us from the last snapshot, from the row of
+            //this very process and not one that had its pid before
+            if let Some(process) = self
+                .processes
+                .iter()
+                .find(|p| p.pid == event.pid && event.start_time == Some(p.start_time))
+            {
+                if event.command.is_empty() {
+                    event.name = process.name.clone();
+                    event.command = process.command.clone();
+                }
+                event.cpu_percent = process.cpu_percent;
+                event.memory_kb = process.memory_kb;
+                if event.cpu_secs.is_none() {
+                    event.cpu_secs = Some(process.cpu_time_secs());
+                }
+            }
+            if let Some(cpu_secs) = event.cpu_secs {
+                let usage = self
+                    .command_cpu
+                    .entry(event.short_command().to_string())
+                    .or_default();
+                usage.runs += 1;
+                usage.cpu_secs += cpu_secs;
+            }
         }
+        self.event_log.push(event);
     }
 
     pub fn select(&mut self, i: usize) {
-        let idx = if i >= self.processes.len() {
-            self.processes.len()
-        } else {
-            i
-        };
+        let idx = i.min(self.row_count().saturating_sub(1));
         self.table_state.select(Some(idx));
     }
 
+    fn row_key(&self, row: TableRow) -> RowKey {
+        match row {
+            TableRow::Process(i) | TableRow::Member(i) => RowKey::of(&self.processes[i]),
+            TableRow::Group(i) => RowKey::Group(self.groups[i].key.clone()),
+            TableRow::Ghost(i) => RowKey::of(&self.ghosts[i].process),
+        }
+    }
+
+    ///Put the selection back on the row showing `key`, or the followed process, after the
+    ///rows moved. A process hidden in a collapsed group selects its group.
+    fn restore_selection(&mut self, key: Option<RowKey>) {
+        let key = match &self.following {
+            Some(followed) => Some(RowKey::Process {
+                host: followed.host.clone(),
+                pid: followed.pid,
+                start_time: followed.start_time,
+            }),
+            None => key,
+        };
+        let Some(key) = key else {
+            return;
+        };
+        //the exit is worth a notice even while its ghost keeps the row
+        if let Some(followed) = &self.following {
+            let alive = self.processes.iter().any(|p| {
+                p.host == followed.host
+                    && p.pid == followed.pid
+                    && p.start_time == followed.start_time
+            });
+            if !alive {
+                let message = format!(
+                    "Followed process {} ({}) exited",
+                    followed.pid, followed.name
+                );
+                self.following = None;
+                self.notify(message);
+            }
+        }
+        let rows = self.table_rows();
+        let found = rows
+            .iter()
+            .position(|&row| self.row_key(row) == key)
+            .or_else(|| {
+                let group = self.groups.iter().position(|g| {
+                    g.members
+                        .iter()
+                        .any(|&m| RowKey::of(&self.processes[m]) == key)
+                })?;
+                rows.iter().position(|&row| row == TableRow::Group(group))
+            });
+        match found {
+            Some(idx) => self.table_state.select(Some(idx)),
+            //stay where we were, as long as that is still a row
+            None => self.select(self.table_state.selected().unwrap_or(0)),
+        }
+    }
+
+    ///Follow the selected process, or stop following
+    fn toggle_follow(&mut self) {
+        if self.following.take().is_some() {
+            self.notify("Stopped following");
+            return;
+        }
+        let Some(process) = self.selected_process() else {
+            return;
+        };
+        let followed = Followed {
+            host: process.host.clone(),
+            pid: process.pid,
+            start_time: process.start_time,
+            name: process.name.clone(),
+        };
+        self.notify(format!("Following {} ({})", followed.pid, followed.name));
+        self.following = Some(followed);
+    }
+
+    ///Keys with modifiers, only Ctrl-P for the palette needs them
+    pub fn handle_key_event(&mut self, key: KeyEvent) {
+        let palette_key = key.modifiers.contains(KeyModifiers::CONTROL)
+            && matches!(key.code, KeyCode::Char('p') | KeyCode::Char('P'));
+        //the palette runs table commands, the overview has no table
+        if palette_key && self.prompt.is_none() && self.host_view != HostView::Overview {
+            self.show_help = false;
+            self.palette = Some(Palette::default());
+            return;
+        }
+        self.handle_key(key.code);
+    }
+
     pub fn handle_key(&mut self, key: KeyCode) {
+        if self.prompt.is_some() {
+            self.handle_prompt_key(key);
+            return;
+        }
+        if self.palette.is_some() {
+            self.handle_palette_key(key);
+            return;
+        }
+        if self.show_help {
+            self.handle_help_key(key);
+            return;
+        }
+        if key == KeyCode::Char('?') {
+            self.show_help = true;
+            self.help_scroll = 0;
+            return;
+        }
+        if self.detail.is_some() {
+            self.handle_detail_key(key);
+            return;
+        }
+        if self.host_view == HostView::Overview {
+            self.handle_overview_key(key);
+            return;
+        }
+        //vim style counts, a leading 0 is not a count
+        if let KeyCode::Char(digit @ '0'..='9') = key
+            && (digit != '0' || self.pending_count.is_some())
+        {
+            let count = self.pending_count.unwrap_or(0);
+            self.pending_count = Some(count * 10 + digit.to_digit(10).unwrap_or(0) as usize);
+            return;
+        }
+        let count = self.pending_count.take();
+        let pending_g = std::mem::take(&mut self.pending_g);
+        let repeat = count.unwrap_or(1);
         match key {
-            KeyCode::Char('q') | KeyCode::Esc => self.should_quit = true,
-            KeyCode::Char('c') | KeyCode::Char('C') => self.handle_sort(SortBy::Cpu),
-            KeyCode::Char('m') | KeyCode::Char('M') => self.handle_sort(SortBy::Memory),
-            KeyCode::Char('p') | KeyCode::Char('P') => self.handle_sort(SortBy::Pid),
-            KeyCode::Char('n') | KeyCode::Char('N') => self.handle_sort(SortBy::Name),
-            KeyCode::Char('o') | KeyCode::Char('O') => self.handle_sort(SortBy::Command),
+            //drop a half typed motion rather than quitting
+            KeyCode::Esc if count.is_some() || pending_g => {}
+            //one host's table is a drill down from the overview
+            KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc if self.remotes.len() > 1 => {
+                self.show_hosts(HostView::Overview)
+            }
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('J') if repeat > 1 => {
+                self.move_selection(repeat as isize)
+            }
+            KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('K') if repeat > 1 => {
+                self.move_selection(-(repeat as isize))
+            }
+            KeyCode::PageDown => self.move_selection((self.page_size * repeat) as isize),
+            KeyCode::PageUp => self.move_selection(-((self.page_size * repeat) as isize)),
+            KeyCode::Home => self.select(0),
+            KeyCode::End => self.select(self.row_count().saturating_sub(1)),
+            //"gg" and "G" go to the top and bottom, or to the row given as a count
+            KeyCode::Char('g') if pending_g => self.select(count.unwrap_or(1).saturating_sub(1)),
+            KeyCode::Char('g') => {
+                self.pending_g = true;
+                self.pending_count = count;
+            }
+            KeyCode::Char('G') => match count {
+                Some(row) => self.select(row.saturating_sub(1)),
+                None => self.select(self.row_count().saturating_sub(1)),
+            },
             KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('J') => self.next_process(),
             KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('K') => self.previous_process(),
+            KeyCode::Char('[') => self.event_log.scroll_up(),
+            KeyCode::Char(']') => self.event_log.scroll_down(),
+            other => {
+                if let Some(command) = Command::for_key(other) {
+                    self.run_command(command);
+                }
+            }
+        }
+    }
+
+    ///Carry out a command from a key or the palette
+    pub fn run_command(&mut self, command: Command) {
+        if self.host_view.is_remote() && command.is_local_only() {
+            self.notify(format!(
+                "{} is only available for this host",
+                command.label()
+            ));
+            return;
+        }
+        match command {
+            Command::Quit => self.should_quit = true,
+            Command::Help => {
+                self.show_help = true;
+                self.help_scroll = 0;
+            }
+            Command::Sort(sort) => self.handle_sort(sort),
+            Command::ReverseSort => self.reverse_sort = !self.reverse_sort,
+            Command::SortPrevious => self.cycle_sort(-1),
+            Command::SortNext => self.cycle_sort(1),
+            Command::Filter => {
+                self.prompt = Some(Prompt::new(
+                    "Filter (e.g. age < 5m, start > 09:00, time > 1h, name ~ ssh)",
+                    self.filter
+                        .as_ref()
+                        .map(|(text, _)| text.clone())
+                        .unwrap_or_default(),
+                    PromptAction::Filter,
+                ))
+            }
+            Command::ClearFilter => {
+                self.filter = None;
+                self.select(0);
+            }
+            Command::JumpToPid => {
+                self.prompt = Some(Prompt::new("Jump to PID", "", PromptAction::JumpToPid))
+            }
+            Command::Parent => self.select_parent(),
+            Command::FirstChild => self.select_first_child(),
+            Command::OpenDetail => self.open_selected(),
+            Command::ToggleGrouping => self.toggle_grouping(),
+            Command::ToggleEvents => self.show_events = !self.show_events,
+            Command::ToggleNetwork => self.show_network = !self.show_network,
+            Command::ToggleDisks => self.toggle_disks(),
+            Command::ToggleSensors => self.show_sensors = !self.show_sensors,
+            Command::ToggleSecurity => {
+                self.show_security = !self.show_security;
+                //labels were not read while nothing showed them
+                self.view_changed = true;
+            }
+            Command::ToggleActivity => self.show_activity = !self.show_activity,
+            Command::ToggleOom => self.show_oom = !self.show_oom,
+            Command::ToggleFollow => self.toggle_follow(),
+            Command::Tag => self.toggle_tag(),
+            Command::TagVisible => self.tag_visible(),
+            Command::TagSubtree => self.tag_subtree(),
+            Command::Untag => {
+                self.tagged.clear();
+                self.notify("Untagged everything");
+            }
+            Command::EditAffinity => self.edit_affinity(),
+            Command::EditOomScoreAdj => self.edit_oom_score_adj(),
+            Command::EditNice => self.edit_nice(),
+            Command::Signal(signal) => self.edit_signal(signal),
+        }
+    }
+
+    ///Keys while the palette is open, everything printable narrows it down
+    fn handle_palette_key(&mut self, key: KeyCode) {
+        let Some(palette) = &mut self.palette else {
+            return;
+        };
+        match key {
+            KeyCode::Esc => self.palette = None,
+            KeyCode::Enter => {
+                let command = palette.selected_command();
+                self.palette = None;
+                if let Some(command) = command {
+                    //commands act on the process table
+                    self.detail = None;
+                    self.run_command(command);
+                }
+            }
+            KeyCode::Down => palette.next(),
+            KeyCode::Up => palette.previous(),
+            KeyCode::Backspace => palette.pop(),
+            KeyCode::Char(c) => palette.push(c),
+            _ => {}
+        }
+    }
+
+    fn handle_help_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Down | KeyCode::Char('j') => self.help_scroll += 1,
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.help_scroll = self.help_scroll.saturating_sub(1)
+            }
+            _ => self.show_help = false,
+        }
+    }
+
+    ///Cycle between no grouping, grouping by command and grouping by executable
+    fn toggle_grouping(&mut self) {
+        self.group_by = match self.group_by {
+            None => Some(GroupBy::Command),
+            Some(GroupBy::Command) => Some(GroupBy::Executable),
+            Some(GroupBy::Executable) => None,
+        };
+        self.expanded_groups.clear();
+        self.regroup();
+        self.select(0);
+    }
+
+    ///Cycle the disk panel between hidden, whole disks and every block device
+    fn toggle_disks(&mut self) {
+        (self.show_disks, self.disks.show_all) = match (self.show_disks, self.disks.show_all) {
+            (false, _) => (true, false),
+            (true, false) => (true, true),
+            (true, true) => (false, false),
+        };
+    }
+
+    ///How many processes are in the given state
+    pub fn count_in_state(&self, state: &ProcessState) -> usize {
+        self.processes.iter().filter(|p| &p.state == state).count()
+    }
+
+    ///Keys of the host overview, Enter drills down into a host or all of them merged
+    fn handle_overview_key(&mut self, key: KeyCode) {
+        let summaries = summarize(&self.remotes, self.host_sort);
+        //the row after the hosts is all of them merged
+        let rows = summaries.len() + 1;
+        let selected = self.host_state.selected().unwrap_or(0).min(rows - 1);
+        match key {
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('J') => {
+                self.host_state.select(Some((selected + 1) % rows))
+            }
+            KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('K') => {
+                self.host_state.select(Some((selected + rows - 1) % rows))
+            }
+            KeyCode::Home => self.host_state.select(Some(0)),
+            KeyCode::End => self.host_state.select(Some(rows - 1)),
+            KeyCode::Enter => match summaries.get(selected) {
+                Some(summary) => self.show_hosts(HostView::Host(summary.index)),
+                None => self.show_hosts(HostView::Merged),
+            },
+            KeyCode::Char('n') | KeyCode::Char('N') => self.sort_hosts(HostSort::Name),
+            KeyCode::Char('c') | KeyCode::Char('C') => self.sort_hosts(HostSort::Cpu),
+            KeyCode::Char('m') | KeyCode::Char('M') => self.sort_hosts(HostSort::Memory),
+            KeyCode::Char('l') | KeyCode::Char('L') => self.sort_hosts(HostSort::Load),
+            KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => self.should_quit = true,
+            _ => {}
+        }
+    }
+
+    ///Sort the overview by `sort`, or back to the given order if it already is
+    fn sort_hosts(&mut self, sort: HostSort) {
+        self.host_sort = match self.host_sort == sort {
+            true => HostSort::Given,
+            false => sort,
+        };
+    }
+
+    ///Switch what the table shows, it starts over as the rows are different processes
+    pub fn show_hosts(&mut self, view: HostView) {
+        self.host_view = view;
+        self.processes.clear();
+        self.ghosts.clear();
+        self.groups.clear();
+        self.tagged.clear();
+        self.following = None;
+        self.select(0);
+        self.view_changed = true;
+    }
+
+    ///Summaries of the agents in the order the overview shows them
+    pub fn host_summaries(&self) -> Vec<HostSummary> {
+        summarize(&self.remotes, self.host_sort)
+    }
+
+    ///Keys while the detail view is open
+    fn handle_detail_key(&mut self, key: KeyCode) {
+        let Some(detail) = &mut self.detail else {
+            return;
+        };
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.detail = None,
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => {
+                detail.switch_tab(detail.tab.next())
+            }
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                detail.switch_tab(detail.tab.previous())
+            }
+            KeyCode::Down | KeyCode::Char('j') => detail.scroll_down(),
+            KeyCode::Up | KeyCode::Char('k') => detail.scroll_up(),
+            KeyCode::Char('r') | KeyCode::Char('R') => detail.reload(),
+            KeyCode::Char('e') | KeyCode::Char('E') if detail.tab == DetailTab::Limits => {
+                if let Some(limit) = detail.selected_limit() {
+                    self.prompt = Some(Prompt::new(
+                        format!("{} (soft hard)", limit.name),
+                        format!(
+                            "{} {}",
+                            format_limit_value(limit.soft),
+                            format_limit_value(limit.hard)
+                        ),
+                        PromptAction::SetLimit {
+                            target: detail.target,
+                            limit: limit.name.clone(),
+                        },
+                    ));
+                }
+            }
             _ => {}
         }
     }
 
+    ///Keys while a prompt is open, everything printable is input
+    fn handle_prompt_key(&mut self, key: KeyCode) {
+        let Some(prompt) = &mut self.prompt else {
+            return;
+        };
+        match key {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Enter => {
+                if let Some(prompt) = self.prompt.take() {
+                    self.submit_prompt(prompt);
+                }
+            }
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Char(c) => prompt.input.push(c),
+            _ => {}
+        }
+    }
+
+    ///Carry out a submitted prompt, reporting how it went in the status bar
+    fn submit_prompt(&mut self, prompt: Prompt) {
+        let result = match &prompt.action {
+            //the detail view may have been open for a long time, its pid could be reused by now
+            PromptAction::SetLimit { target, limit } => parse_limit_input(&prompt.input)
+                .and_then(|(soft, hard)| {
+                    check_target(target)?;
+                    set_limit(target.pid, limit, soft, hard)
+                })
+                .map(|_| {
+                    if let Some(detail) = &mut self.detail {
+                        detail.reload();
+                    }
+                    format!("{limit} of {} set to {}", target.pid, prompt.input.trim())
+                }),
+            PromptAction::SetAffinity { targets } => {
+                parse_affinity_input(&prompt.input).and_then(|(cpus, all_threads)| {
+                    let mut threads = 0;
+                    apply_to_targets(targets, |pid| {
+                        threads += set_affinity(pid, &cpus, all_threads)?;
+                        Ok(())
+                    })
+                    .map(|done| {
+                        format!(
+                            "Pinned {threads} thread(s) of {} to {}",
+                            describe_done(targets, done),
+                            prompt.input.trim()
+                        )
+                    })
+                })
+            }
+            PromptAction::Renice { host, targets } => parse_nice(&prompt.input).and_then(|nice| {
+                match self.remote_for(host)? {
+                    //the agent answers once it is done
+                    Some(remote) => remote
+                        .send(&ClientMessage::Renice {
+                            targets: targets.clone(),
+                            nice,
+                        })
+                        .map(|_| format!("Asked {} to renice", remote.hostname)),
+                    None => apply_to_targets(targets, |pid| set_nice(pid, nice).map(|_| ()))
+                        .map(|done| format!("Reniced {} to {nice}", describe_done(targets, done))),
+                }
+            }),
+            PromptAction::SetOomScoreAdj { target } => parse_oom_score_adj(&prompt.input)
+                .and_then(|adj| {
+                    check_target(target)?;
+                    set_oom_score_adj(target.pid, adj).map(|_| adj)
+                })
+                .map(|adj| format!("oom_score_adj of {} set to {adj}", target.pid)),
+            PromptAction::SendSignal { host, targets } => {
+                parse_signal(&prompt.input).and_then(|signal| match self.remote_for(host)? {
+                    Some(remote) => remote
+                        .send(&ClientMessage::Signal {
+                            targets: targets.clone(),
+                            signal: signal.as_str().to_string(),
+                        })
+                        .map(|_| format!("Asked {} to send {}", remote.hostname, signal.as_str())),
+                    None => apply_to_targets(targets, |pid| send_signal(pid, signal)).map(|done| {
+                        format!(
+                            "Sent {} to {}",
+                            signal.as_str(),
+                            describe_done(targets, done)
+                        )
+                    }),
+                })
+            }
+            PromptAction::JumpToPid => match prompt.input.trim().parse::<u64>() {
+                Ok(pid) if self.select_pid(pid) => Ok(format!("Jumped to {pid}")),
+                Ok(pid) => Err(anyhow!("{pid} is not in the table")),
+                Err(_) => Err(anyhow!("'{}' is not a pid", prompt.input.trim())),
+            },
+            PromptAction::Filter => {
+                let text = prompt.input.trim();
+                let result = if text.is_empty() {
+                    self.filter = None;
+                    Ok("Filter cleared".to_string())
+                } else {
+                    Predicate::parse(text).map(|predicate| {
+                        self.filter = Some((text.to_string(), predicate));
+                        format!("Showing processes where {text}")
+                    })
+                };
+                self.select(0);
+                result
+            }
+        };
+        match result {
+            Ok(message) => self.notify(message),
+            Err(e) => self.notify(format!("{}: {e}", prompt.label)),
+        }
+    }
+
+    ///Expand or collapse the group under the selection, or open the detail view of a process
+    fn open_selected(&mut self) {
+        match self.selected_row() {
+            Some(TableRow::Group(idx)) => {
+                let key = self.groups[idx].key.clone();
+                if !self.expanded_groups.remove(&key) {
+                    self.expanded_groups.insert(key);
+                }
+            }
+            Some(TableRow::Process(_)) | Some(TableRow::Member(_))
+                if self.host_view.is_remote() =>
+            {
+                self.notify("Details are only available for processes on this host");
+            }
+            Some(TableRow::Process(_)) | Some(TableRow::Member(_)) => {
+                self.detail = self.selected_process().map(ProcessDetail::load);
+            }
+            Some(TableRow::Ghost(_)) | None => {}
+        }
+    }
+
+    fn regroup(&mut self) {
+        match &self.group_by {
+            Some(group_by) => {
+                self.groups = group_processes(&self.processes, group_by);
+                sort_groups(
+                    &mut self.groups,
+                    &self.processes,
+                    &self.sort_by,
+                    self.reverse_sort,
+                );
+            }
+            None => self.groups.clear(),
+        }
+    }
+
+    ///Whether the process passes the filter, if there is one
+    fn is_visible(&self, idx: usize) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|(_, predicate)| predicate.matches(&self.processes[idx]))
+    }
+
+    ///The rows of the process table, in display order
+    pub fn table_rows(&self) -> Vec<TableRow> {
+        let ghosts = (0..self.ghosts.len()).filter(|&i| {
+            self.filter
+                .as_ref()
+                .is_none_or(|(_, predicate)| predicate.matches(&self.ghosts[i].process))
+        });
+        if self.group_by.is_none() {
+            let live = (0..self.processes.len())
+                .filter(|&i| self.is_visible(i))
+                .map(TableRow::Process);
+            return self.merge_ghosts(live.collect(), ghosts.collect());
+        }
+        let mut rows = Vec::new();
+        for (i, group) in self.groups.iter().enumerate() {
+            let members: Vec<usize> = group
+                .members
+                .iter()
+                .copied()
+                .filter(|&m| self.is_visible(m))
+                .collect();
+            //groups without a single matching member are filtered out with them
+            if members.is_empty() {
+                continue;
+            }
+            rows.push(TableRow::Group(i));
+            if self.expanded_groups.contains(&group.key) {
+                rows.extend(members.into_iter().map(TableRow::Member));
+            }
+        }
+        //groups are for what is running, the exited go underneath them
+        rows.extend(ghosts.map(TableRow::Ghost));
+        rows
+    }
+
+    ///Put each ghost where it would sort among the live rows, pid order when unsorted
+    fn merge_ghosts(&self, live: Vec<TableRow>, mut ghosts: Vec<usize>) -> Vec<TableRow> {
+        let sort = self.sort_by.clone().unwrap_or(SortBy::Pid);
+        let order = |a: &ProcessInfo, b: &ProcessInfo| {
+            let ordering = compare_processes(a, b, &sort);
+            //unsorted rows are reversed too, from /proc's pid order
+            if self.reverse_sort {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        };
+        ghosts.sort_by(|&a, &b| order(&self.ghosts[a].process, &self.ghosts[b].process));
+        let mut rows = Vec::with_capacity(live.len() + ghosts.len());
+        let mut ghosts = ghosts.into_iter().peekable();
+        for row in live {
+            if let TableRow::Process(i) = row {
+                while let Some(&g) = ghosts.peek()
+                    && order(&self.ghosts[g].process, &self.processes[i]) == Ordering::Less
+                {
+                    rows.push(TableRow::Ghost(g));
+                    ghosts.next();
+                }
+            }
+            rows.push(row);
+        }
+        rows.extend(ghosts.map(TableRow::Ghost));
+        rows
+    }
+
+    ///Whether the process started within the highlight window
+    pub fn is_new(&self, process: &ProcessInfo) -> bool {
+        !self.new_highlight.is_zero() && process.age_seconds() < self.new_highlight.as_secs()
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.table_rows().len()
+    }
+
+    pub fn selected_row(&self) -> Option<TableRow> {
+        self.table_state
+            .selected()
+            .and_then(|i| self.table_rows().get(i).copied())
+    }
+
+    ///The process under the selection, if it is on a process rather than a group
+    pub fn selected_process(&self) -> Option<&ProcessInfo> {
+        match self.selected_row()? {
+            TableRow::Process(i) | TableRow::Member(i) => self.processes.get(i),
+            TableRow::Group(_) | TableRow::Ghost(_) => None,
+        }
+    }
+
     ///Set the sort by
     fn handle_sort(&mut self, sort: SortBy) {
         let curr_sort = self.sort_by.clone().unwrap_or(SortBy::Cpu);
@@ -74,13 +995,251 @@ impl App {
             self.sort_by = Some(sort);
             self.should_go_to_top = true;
             self.reverse_sort = false;
+            self.show_sort_column();
+        }
+    }
+
+    ///Sorting by a hidden column shows it, there is no telling the order otherwise
+    fn show_sort_column(&mut self) {
+        match self.sort_by {
+            Some(
+                SortBy::MinorFaults
+                | SortBy::MajorFaults
+                | SortBy::VoluntarySwitches
+                | SortBy::InvoluntarySwitches,
+            ) => self.show_activity = true,
+            Some(SortBy::OomScore | SortBy::OomScoreAdj) => self.show_oom = true,
+            _ => {}
+        }
+    }
+
+    pub fn is_tagged(&self, process: &ProcessInfo) -> bool {
+        self.tagged.contains(&RowKey::of(process))
+    }
+
+    ///Visible processes in table order, the members of groups included
+    fn visible_processes(&self) -> Vec<usize> {
+        match self.group_by {
+            None => (0..self.processes.len())
+                .filter(|&i| self.is_visible(i))
+                .collect(),
+            Some(_) => self
+                .groups
+                .iter()
+                .flat_map(|g| g.members.iter().copied())
+                .filter(|&i| self.is_visible(i))
+                .collect(),
+        }
+    }
+
+    ///Tag the selected process, or untag it if it was, and move on to the next row.
+    ///On a group every shown member is tagged, or untagged if they all were.
+    fn toggle_tag(&mut self) {
+        let members: Vec<RowKey> = match self.selected_row() {
+            Some(TableRow::Process(i)) | Some(TableRow::Member(i)) => {
+                vec![RowKey::of(&self.processes[i])]
+            }
+            Some(TableRow::Group(g)) => self.groups[g]
+                .members
+                .iter()
+                .filter(|&&m| self.is_visible(m))
+                .map(|&m| RowKey::of(&self.processes[m]))
+                .collect(),
+            Some(TableRow::Ghost(_)) | None => return,
+        };
+        if members.iter().all(|key| self.tagged.contains(key)) {
+            members.iter().for_each(|key| {
+                self.tagged.remove(key);
+            });
+        } else {
+            self.tagged.extend(members);
+        }
+        self.move_selection(1);
+    }
+
+    ///Tag everything that passes the filter
+    fn tag_visible(&mut self) {
+        let keys: Vec<RowKey> = self
+            .visible_processes()
+            .into_iter()
+            .map(|i| RowKey::of(&self.processes[i]))
+            .collect();
+        let count = keys.len();
+        self.tagged.extend(keys);
+        self.notify(format!(
+            "Tagged {count} processes, {} in all",
+            self.tagged.len()
+        ));
+    }
+
+    fn tag_subtree(&mut self) {
+        let Some((host, root)) = self.selected_process().map(|p| (p.host.clone(), p.pid)) else {
+            return;
+        };
+        //pids only mean something on their own host
+        let on_host = |p: &&ProcessInfo| p.host == host;
+        let mut pids = vec![root];
+        let mut next = 0;
+        while next < pids.len() {
+            let parent = pids[next];
+            pids.extend(
+                self.processes
+                    .iter()
+                    .filter(on_host)
+                    .filter(|p| p.ppid == parent && p.pid != parent)
+                    .map(|p| p.pid),
+            );
+            next += 1;
+        }
+        let keys: Vec<RowKey> = self
+            .processes
+            .iter()
+            .filter(on_host)
+            .filter(|p| pids.contains(&p.pid))
+            .map(RowKey::of)
+            .collect();
+        self.tagged.extend(keys);
+        self.notify(format!("Tagged {root} and {} descendants", pids.len() - 1));
+    }
+
+    ///What bulk actions apply to, the tagged processes or else the selected one
+    pub fn targets(&self) -> Vec<&ProcessInfo> {
+        if self.tagged.is_empty() {
+            return self.selected_process().into_iter().collect();
+        }
+        self.visible_processes()
+            .into_iter()
+            .map(|i| &self.processes[i])
+            .chain(
+                self.processes
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| !self.is_visible(*i))
+                    .map(|(_, p)| p),
+            )
+            .filter(|p| self.is_tagged(p))
+            .collect()
+    }
+
+    ///Host of the targets, None for this one. Actions go to one agent at a time,
+    ///so targets on several hosts get a notice instead.
+    fn target_host(targets: &[&ProcessInfo]) -> Result<Option<String>> {
+        let host = targets.first().and_then(|p| p.host.clone());
+        if targets.iter().any(|p| p.host != host) {
+            bail!("Tagged processes are on several hosts, act on one host at a time");
+        }
+        Ok(host)
+    }
+
+    ///The agent to send actions for processes on `host` to, None for this host
+    fn remote_for(&self, host: &Option<String>) -> Result<Option<&Remote>> {
+        match host {
+            None => Ok(None),
+            Some(host) => (0..self.remotes.len())
+                .find(|&i| &host_label(&self.remotes, i) == host)
+                .map(|i| Some(&self.remotes[i]))
+                .ok_or_else(|| anyhow!("Not connected to {host}")),
+        }
+    }
+
+    ///"1234 sshd" for one target, "12 tagged processes" for more
+    fn describe_targets(targets: &[&ProcessInfo]) -> String {
+        match targets {
+            [process] => format!("{} {}", process.pid, process.name),
+            _ => format!("{} tagged processes", targets.len()),
+        }
+    }
+
+    ///Ask for new cpus for the targets, starting from the current ones of the first
+    fn edit_affinity(&mut self) {
+        let targets = self.targets();
+        let Some(first) = targets.first() else {
+            return;
+        };
+        self.prompt = Some(Prompt::new(
+            format!(
+                "CPUs for {} (e.g. 0-3,8, add 'main' for the main thread only)",
+                Self::describe_targets(&targets)
+            ),
+            first.cpus_allowed.clone(),
+            PromptAction::SetAffinity {
+                targets: targets.iter().map(|p| Target::of(p)).collect(),
+            },
+        ));
+    }
+
+    fn edit_nice(&mut self) {
+        let targets = self.targets();
+        let Some(first) = targets.first() else {
+            return;
+        };
+        let host = match Self::target_host(&targets) {
+            Ok(host) => host,
+            Err(e) => return self.notify(e.to_string()),
+        };
+        self.prompt = Some(Prompt::new(
+            format!(
+                "Nice for {} (-20 first .. 19 last)",
+                Self::describe_targets(&targets)
+            ),
+            first.nice.to_string(),
+            PromptAction::Renice {
+                host,
+                targets: targets.iter().map(|p| Target::of(p)).collect(),
+            },
+        ));
+    }
+
+    ///Ask for a new oom_score_adj for the selected process
+    fn edit_oom_score_adj(&mut self) {
+        let Some(process) = self.selected_process() else {
+            return;
+        };
+        self.prompt = Some(Prompt::new(
+            format!(
+                "oom_score_adj for {} {} (-1000 never kill .. 1000 kill first)",
+                process.pid, process.name
+            ),
+            process.oom_score_adj.to_string(),
+            PromptAction::SetOomScoreAdj {
+                target: Target::of(process),
+            },
+        ));
+    }
+
+    ///Ask which signal to send to the targets, Enter on the suggestion confirms it
+    fn edit_signal(&mut self, signal: Signal) {
+        let targets = self.targets();
+        if targets.is_empty() {
+            return;
         }
+        let host = match Self::target_host(&targets) {
+            Ok(host) => host,
+            Err(e) => return self.notify(e.to_string()),
+        };
+        self.prompt = Some(Prompt::new(
+            format!("Signal to send to {}", Self::describe_targets(&targets)),
+            signal.as_str(),
+            PromptAction::SendSignal {
+                host,
+                targets: targets.iter().map(|p| Target::of(p)).collect(),
+            },
+        ));
+    }
+
+    ///Move the sort to the column left (-1) or right (1) of the current one
+    fn cycle_sort(&mut self, offset: isize) {
+        let curr_sort = self.sort_by.clone().unwrap_or(SortBy::Cpu);
+        self.sort_by = Some(curr_sort.cycle(offset));
+        self.should_go_to_top = true;
+        self.reverse_sort = false;
+        self.show_sort_column();
     }
 
     pub fn next_process(&mut self) {
         let i = match self.table_state.selected() {
             Some(i) => {
-                if i >= self.processes.len() - 1 {
+                if i + 1 >= self.row_count() {
                     0
                 } else {
                     i + 1
@@ -95,7 +1254,7 @@ impl App {
         let i = match self.table_state.selected() {
             Some(i) => {
                 if i == 0 {
-                    self.processes.len() - 1
+                    self.row_count().saturating_sub(1)
                 } else {
                     i - 1
                 }
@@ -105,58 +1264,537 @@ impl App {
         self.table_state.select(Some(i));
     }
 
+    ///Move the selection by `delta` rows, stopping at either end rather than wrapping
+    pub fn move_selection(&mut self, delta: isize) {
+        let current = self.table_state.selected().unwrap_or(0);
+        self.select(current.saturating_add_signed(delta));
+    }
+
+    ///Select the row of a process, expanding its group if it is collapsed.
+    ///False when the process is gone or filtered out.
+    pub fn select_pid(&mut self, pid: u64) -> bool {
+        self.select_matching(|p| p.pid == pid)
+    }
+
+    ///Select the row of the first process `matches` picks, like `select_pid`
+    fn select_matching(&mut self, matches: impl Fn(&ProcessInfo) -> bool) -> bool {
+        let Some(idx) = self.processes.iter().position(matches) else {
+            return false;
+        };
+        if let Some(group) = self.groups.iter().find(|g| g.members.contains(&idx)) {
+            self.expanded_groups.insert(group.key.clone());
+        }
+        match self
+            .table_rows()
+            .iter()
+            .position(|&row| row == TableRow::Process(idx) || row == TableRow::Member(idx))
+        {
+            Some(row) => {
+                self.table_state.select(Some(row));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn select_parent(&mut self) {
+        let Some(process) = self.selected_process() else {
+            return;
+        };
+        let (host, pid, ppid) = (process.host.clone(), process.pid, process.ppid);
+        if !self.select_matching(|p| p.pid == ppid && p.host == host) {
+            self.notify(format!("Parent {ppid} of {pid} is not shown"));
+        }
+    }
+
+    ///The oldest child, the one a service manager or shell started first
+    fn select_first_child(&mut self) {
+        let Some((host, pid)) = self.selected_process().map(|p| (p.host.clone(), p.pid)) else {
+            return;
+        };
+        let child = self
+            .processes
+            .iter()
+            .filter(|p| p.ppid == pid && p.host == host)
+            .min_by_key(|p| p.start_time)
+            .map(|p| p.pid);
+        match child {
+            Some(child) if self.select_matching(|p| p.pid == child && p.host == host) => {}
+            Some(child) => self.notify(format!("Child {child} of {pid} is not shown")),
+            None => self.notify(format!("{pid} has no children")),
+        }
+    }
+
+    ///Processes of the agents the table shows, None when none of them sent anything new
+    fn poll_remotes(&mut self) -> Option<Vec<ProcessInfo>> {
+        let mut fresh = std::mem::take(&mut self.view_changed);
+        for i in 0..self.remotes.len() {
+            let update = self.remotes[i].poll();
+            for notice in update.notices {
+                self.notify(notice);
+            }
+            fresh |= update.fresh && self.host_view.shows(i);
+        }
+        if !fresh {
+            return None;
+        }
+        match self.host_view {
+            HostView::Host(i) => {
+                let remote = self.remotes.get(i)?;
+                self.pressure.record(
+                    remote
+                        .snapshot
+                        .as_ref()
+                        .and_then(|s| s.system.pressure.clone()),
+                );
+                Some(remote.processes(&host_label(&self.remotes, i)))
+            }
+            //pressure of several hosts added up means nothing
+            HostView::Merged => {
+                self.pressure.record(None);
+                Some(
+                    (0..self.remotes.len())
+                        .flat_map(|i| self.remotes[i].processes(&host_label(&self.remotes, i)))
+                        .collect(),
+                )
+            }
+            HostView::Local | HostView::Overview => None,
+        }
+    }
+
+    ///Processes of this host, with rates worked out against the last refresh
+    fn read_processes(&mut self) -> Result<Vec<ProcessInfo>> {
+        let mut new_processes = get_process_info(&mut self.user_cache)?;
+        for new_process in &mut new_processes {
+            if let Some(process) = self.processes.iter().find(|f| f.pid == new_process.pid) {
+                new_process.last_cpu_time = process.last_cpu_time;
+                new_process.last_measurement = process.last_measurement;
+                new_process.last_activity = process.last_activity;
+            }
+            update_activity_rates(new_process);
+            update_cpu_percent(new_process);
+        }
+        if self.needs_labels() {
+            for process in &mut new_processes {
+                process.security.label = read_lsm_label(process.pid);
+            }
+        }
+        Ok(new_processes)
+    }
+
+    ///Whether anything shows or tests LSM labels, which take two more reads per process
+    fn needs_labels(&self) -> bool {
+        self.show_security
+            || self.read_labels
+            || self
+                .filter
+                .as_ref()
+                .is_some_and(|(_, predicate)| predicate.field == Field::Label)
+            || self
+                .rules
+                .as_ref()
+                .is_some_and(|rules| rules.uses(Field::Label))
+    }
+
     pub fn update_processes(&mut self) -> Result<()> {
-        if self.last_refresh.elapsed().as_millis() >= 250 {
-            let mut new_processes = get_process_info(&mut self.user_cache)?;
+        if let Some(connector) = &self.proc_connector {
+            for event in connector.drain() {
+                self.record_event(event);
+            }
+        }
+        if self.last_refresh.elapsed().as_millis() >= 250 || self.view_changed {
+            let new_processes = match self.host_view {
+                HostView::Local => self.read_processes()?,
+                //nothing new from the agents yet
+                _ => match self.poll_remotes() {
+                    Some(processes) => processes,
+                    None => return Ok(()),
+                },
+            };
+            //rows are about to move, remember what was selected rather than where
+            let selected = self.selected_row().map(|row| self.row_key(row));
 
-            for new_process in &mut new_processes {
-                if let Some(process) = self.processes.iter().find(|f| f.pid == new_process.pid) {
-                    new_process.last_cpu_time = process.last_cpu_time;
-                    new_process.last_measurement = process.last_measurement;
+            //the first snapshot would report everything as started,
+            //and the proc connector already reports everything as it happens
+            if !self.processes.is_empty() && self.proc_connector.is_none() {
+                for event in diff_snapshots(&self.processes, &new_processes) {
+                    self.record_event(event);
                 }
-                update_cpu_percent(new_process);
             }
 
+            let alive: HashSet<RowKey> = new_processes.iter().map(RowKey::of).collect();
+            let now = Instant::now();
+            if !self.ghost_duration.is_zero() {
+                let exited = std::mem::take(&mut self.processes)
+                    .into_iter()
+                    .filter(|p| !alive.contains(&RowKey::of(p)))
+                    .map(|process| Ghost {
+                        process,
+                        exited_at: now,
+                    });
+                self.ghosts.extend(exited);
+            }
+            self.ghosts
+                .retain(|g| now.duration_since(g.exited_at) < self.ghost_duration);
             self.processes = new_processes;
-            match self.sort_by {
-                Some(SortBy::Cpu) => self.processes.sort_by(|a, b| {
-                    a.cpu_percent
-                        .partial_cmp(&b.cpu_percent)
-                        .unwrap_or(std::cmp::Ordering::Equal)
-                }),
-                Some(SortBy::Memory) => self.processes.sort_by(|a, b| {
-                    a.memory_kb
-                        .partial_cmp(&b.memory_kb)
-                        .unwrap_or(Ordering::Equal)
-                }),
-                Some(SortBy::Pid) => self
-                    .processes
-                    .sort_by(|a, b| a.pid.partial_cmp(&b.pid).unwrap_or(Ordering::Equal)),
-                Some(SortBy::Name) => self
-                    .processes
-                    .sort_by(|a, b| a.name.partial_cmp(&b.name).unwrap_or(Ordering::Equal)),
-                Some(SortBy::Command) => self
-                    .processes
-                    .sort_by(|a, b| a.command.partial_cmp(&b.command).unwrap_or(Ordering::Equal)),
-                None => {}
+            //tags of processes that exited go with them
+            self.tagged.retain(|key| alive.contains(key));
+            if let Some(sort) = &self.sort_by {
+                self.processes.sort_by(|a, b| compare_processes(a, b, sort));
             }
             if self.reverse_sort {
                 self.processes.reverse();
             }
+            self.regroup();
+
+            //system panels are best effort, the process table is what matters,
+            //and they describe this host, not a remote one
+            if !self.host_view.is_remote() {
+                let _ = self.network.update();
+                let _ = self.disks.update();
+                self.pressure.update();
+                if self.show_sensors {
+                    self.sensors = read_sensors(Path::new("/sys"));
+                }
+                self.selected_cgroup_pressure = self
+                    .selected_process()
+                    .and_then(|p| process_cgroup(p.pid))
+                    //the root cgroup is the same as the system wide line
+                    .filter(|cgroup| cgroup != "/")
+                    .and_then(|cgroup| {
+                        read_cgroup_pressure(&cgroup)
+                            .ok()
+                            .map(|pressure| (cgroup, pressure))
+                    });
+            }
 
-            if self.refresh_count % 100 == 0 {
+            if let Some(rules) = &mut self.rules {
+                for toast in rules.evaluate(&self.processes) {
+                    self.notice = Some((toast, Instant::now()));
+                }
+            }
+
+            if self.refresh_count.is_multiple_of(100) {
                 self.user_cache = UsersCache::new();
                 self.refresh_count = 0;
             } else {
                 self.refresh_count += 1;
             }
             self.last_refresh = Instant::now();
-            if self.should_go_to_top {
+            //a new sort shows its top, unless we are following a process
+            if self.should_go_to_top && self.following.is_none() {
                 self.select(0);
-                self.should_go_to_top = false;
+            } else {
+                self.restore_selection(selected);
             }
+            self.should_go_to_top = false;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        model::HostClock,
+        system::{Snapshot, SystemStats},
+    };
+
+    fn process(pid: u64, start_time: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            start_time,
+            name: format!("p{pid}"),
+            ..ProcessInfo::new()
+        }
+    }
+
+    #[test]
+    pub fn test_selection_follows_pid() {
+        let mut app = App::new();
+        app.processes = vec![process(1, 10), process(2, 20), process(3, 30)];
+        app.select(1);
+        let selected = app.selected_row().map(|row| app.row_key(row));
+
+        //re-sorted, pid 2 is now last
+        app.processes = vec![process(3, 30), process(1, 10), process(2, 20)];
+        app.restore_selection(selected.clone());
+        assert_eq!(app.selected_process().map(|p| p.pid), Some(2));
+
+        //a reused pid is a different process, the selection stays put
+        app.processes = vec![process(3, 30), process(2, 99), process(1, 10)];
+        app.restore_selection(selected);
+        assert_eq!(app.table_state.selected(), Some(2));
+    }
+
+    #[test]
+    pub fn test_exit_filled_in_from_its_own_row() {
+        let mut app = App::new();
+        let mut old = process(7, 70);
+        old.command = "/usr/bin/make all".to_string();
+        old.memory_kb = 500;
+        app.processes = vec![old];
+        //the kernel reports a later process that got pid 7, it knows nothing of the old one
+        let exit = |start_time| LifecycleEvent {
+            start_time: Some(start_time),
+            ..LifecycleEvent::blank(LifecycleKind::Exited, 7, "", "")
+        };
+        app.record_event(exit(71));
+        assert_eq!(app.event_log.events[0].memory_kb, 0);
+        assert!(app.command_cpu.is_empty());
+        app.record_event(exit(70));
+        assert_eq!(app.event_log.events[1].memory_kb, 500);
+        assert_eq!(app.command_cpu["make"].runs, 1);
+    }
+
+    #[test]
+    pub fn test_stale_targets_are_refused() {
+        let mut app = App::new();
+        //our own pid, as if it was picked when another process had it
+        let target = Target {
+            pid: std::process::id() as u64,
+            start_time: 1,
+        };
+        let action = PromptAction::SetLimit {
+            target,
+            limit: "Max cpu time".to_string(),
+        };
+        app.submit_prompt(Prompt::new("Max cpu time", "unlimited unlimited", action));
+        let notice = app
+            .notice
+            .take()
+            .map(|(notice, _)| notice)
+            .unwrap_or_default();
+        assert!(notice.ends_with("its pid was reused"), "{notice}");
+        let action = PromptAction::SetOomScoreAdj { target };
+        app.submit_prompt(Prompt::new("oom_score_adj", "0", action));
+        let notice = app.notice.map(|(notice, _)| notice).unwrap_or_default();
+        assert!(notice.ends_with("its pid was reused"), "{notice}");
+    }
+
+    #[test]
+    pub fn test_followed_exit_is_noticed() {
+        let mut app = App::new();
+        app.processes = vec![process(1, 10), process(2, 20)];
+        app.select(1);
+        app.toggle_follow();
+        assert!(app.following.is_some());
+
+        app.processes = vec![process(1, 10)];
+        app.restore_selection(None);
+        assert!(app.following.is_none());
+        assert_eq!(app.current_notice(), Some("Followed process 2 (p2) exited"));
+        assert_eq!(app.table_state.selected(), Some(0));
+    }
+
+    #[test]
+    pub fn test_navigation() {
+        let mut app = App::new();
+        //nothing to move through must not panic
+        app.next_process();
+        app.previous_process();
+        app.handle_key(KeyCode::Char('G'));
+
+        app.processes = (1..=20).map(|pid| process(pid, pid)).collect();
+        app.processes[4].ppid = 1;
+        app.processes[2].ppid = 1;
+        app.select(0);
+        app.handle_key(KeyCode::Char('5'));
+        app.handle_key(KeyCode::Char('j'));
+        assert_eq!(app.table_state.selected(), Some(5));
+        app.handle_key(KeyCode::Char('G'));
+        assert_eq!(app.table_state.selected(), Some(19));
+        app.handle_key(KeyCode::Char('3'));
+        app.handle_key(KeyCode::Char('g'));
+        app.handle_key(KeyCode::Char('g'));
+        assert_eq!(app.table_state.selected(), Some(2));
+        app.page_size = 8;
+        app.handle_key(KeyCode::PageUp);
+        assert_eq!(app.table_state.selected(), Some(0));
+        app.handle_key(KeyCode::PageDown);
+        assert_eq!(app.table_state.selected(), Some(8));
+
+        //the oldest child of pid 1 is pid 3, and back up to its parent
+        app.handle_key(KeyCode::Home);
+        app.handle_key(KeyCode::Char('l'));
+        assert_eq!(app.selected_process().map(|p| p.pid), Some(3));
+        app.handle_key(KeyCode::Char('h'));
+        assert_eq!(app.selected_process().map(|p| p.pid), Some(1));
+
+        assert!(app.select_pid(17));
+        assert_eq!(app.table_state.selected(), Some(16));
+        assert!(!app.select_pid(99));
+    }
+
+    #[test]
+    pub fn test_ghosts_sort_in_place() {
+        let mut app = App::new();
+        app.processes = vec![process(1, 1), process(3, 3), process(5, 5)];
+        app.ghosts = vec![4, 0]
+            .into_iter()
+            .map(|pid| Ghost {
+                process: process(pid, pid),
+                exited_at: Instant::now(),
+            })
+            .collect();
+        assert_eq!(
+            app.table_rows(),
+            vec![
+                TableRow::Ghost(1),
+                TableRow::Process(0),
+                TableRow::Process(1),
+                TableRow::Ghost(0),
+                TableRow::Process(2),
+            ]
+        );
+        //a ghost is shown but can't be acted on
+        app.select(0);
+        assert!(app.selected_process().is_none());
+
+        app.sort_by = Some(SortBy::Pid);
+        app.reverse_sort = true;
+        app.processes.reverse();
+        assert_eq!(app.table_rows()[1], TableRow::Ghost(0));
+        app.sort_by = None;
+        assert_eq!(
+            app.table_rows(),
+            vec![
+                TableRow::Process(0),
+                TableRow::Ghost(0),
+                TableRow::Process(1),
+                TableRow::Process(2),
+                TableRow::Ghost(1),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_sorting_shows_hidden_columns() {
+        let mut app = App::new();
+        assert!(!app.show_activity && !app.show_oom);
+        //cpu, then memory, start, age, time and minor faults
+        for _ in 0..5 {
+            app.handle_key(KeyCode::Char('>'));
         }
+        assert_eq!(app.sort_by, Some(SortBy::MinorFaults));
+        assert!(app.show_activity && !app.show_oom);
+        app.handle_key(KeyCode::Char('v'));
+        assert!(!app.show_activity);
+        app.handle_key(KeyCode::Char('y'));
+        assert!(app.show_oom);
+    }
+
+    #[test]
+    pub fn test_labels_read_on_demand() -> Result<()> {
+        let mut app = App::new();
+        assert!(!app.needs_labels());
+        app.rules = Some(RuleEngine::parse("cpu > 90 => toast")?);
+        assert!(!app.needs_labels());
+        app.rules = Some(RuleEngine::parse("count(label ~ unconfined) > 0 => toast")?);
+        assert!(app.needs_labels());
+        app.rules = None;
+        app.filter = Some(("label ~ x".to_string(), Predicate::parse("label ~ x")?));
+        assert!(app.needs_labels());
+        app.filter = None;
+        app.handle_key(KeyCode::Char('s'));
+        assert!(app.show_security && app.needs_labels());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_tagging() {
+        let mut app = App::new();
+        app.processes = (1..=6).map(|pid| process(pid, pid)).collect();
+        //1 started 2 and 3, 3 started 4
+        app.processes[1].ppid = 1;
+        app.processes[2].ppid = 1;
+        app.processes[3].ppid = 3;
+        app.select(5);
+        assert_eq!(app.targets().len(), 1);
+
+        app.select_pid(3);
+        app.handle_key(KeyCode::Char('+'));
+        let pids = |app: &App| app.targets().iter().map(|p| p.pid).collect::<Vec<_>>();
+        assert_eq!(pids(&app), vec![3, 4]);
+        app.select_pid(1);
+        app.handle_key(KeyCode::Char(' '));
+        assert_eq!(pids(&app), vec![1, 3, 4]);
+        assert_eq!(app.selected_process().map(|p| p.pid), Some(2));
+
+        app.handle_key(KeyCode::Char('u'));
+        app.filter = Some(("pid > 4".to_string(), Predicate::parse("pid > 4").unwrap()));
+        app.handle_key(KeyCode::Char('*'));
+        assert_eq!(pids(&app), vec![5, 6]);
+
+        let failed = apply_to_all(&[1, 2, 3], |pid| match pid {
+            2 => Err(anyhow!("no")),
+            _ => Ok(()),
+        });
+        assert_eq!(failed.unwrap_err().to_string(), "1 of 3 failed, first: no");
+    }
+    #[test]
+    pub fn test_hosts() -> Result<()> {
+        //the same pids on two hosts, pid 2 a child of 1
+        let host = |name: &str| {
+            let mut child = process(2, 20);
+            child.ppid = 1;
+            Remote::offline(
+                name,
+                Snapshot {
+                    timestamp: 0,
+                    clock: HostClock {
+                        boot_time: 0,
+                        clock_ticks: 100.0,
+                    },
+                    system: SystemStats::default(),
+                    processes: vec![process(1, 10), child],
+                },
+            )
+        };
+        let mut app = App::new();
+        app.remotes = vec![host("build1"), host("build2")];
+        app.show_hosts(HostView::Overview);
+        app.update_processes()?;
+        assert!(app.processes.is_empty());
+
+        //the last row of the overview is every host merged
+        app.handle_key(KeyCode::End);
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.host_view, HostView::Merged);
+        app.update_processes()?;
+        assert_eq!(app.table_rows().len(), 4);
+        app.handle_key(KeyCode::Char('*'));
+        assert_eq!(app.targets().len(), 4);
+        app.handle_key(KeyCode::Char('x'));
+        assert!(app.prompt.is_none());
+        //parents are looked for on the same host
+        app.handle_key(KeyCode::Char('u'));
+        app.select_matching(|p| p.pid == 2 && p.host.as_deref() == Some("build2"));
+        app.handle_key(KeyCode::Char('h'));
+        let selected = app.selected_process().map(|p| (p.pid, p.host.clone()));
+        assert_eq!(selected, Some((1, Some("build2".to_string()))));
 
+        app.handle_key(KeyCode::Char('q'));
+        assert_eq!(app.host_view, HostView::Overview);
+        assert!(!app.should_quit);
+        app.handle_key(KeyCode::Home);
+        app.handle_key(KeyCode::Down);
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.host_view, HostView::Host(1));
+        app.update_processes()?;
+        assert_eq!(app.processes.len(), 2);
+        assert!(
+            app.processes
+                .iter()
+                .all(|p| p.host.as_deref() == Some("build2"))
+        );
+        app.handle_key(KeyCode::Char('x'));
+        assert_eq!(
+            app.prompt.map(|p| p.action.host().map(String::from)),
+            Some(Some("build2".to_string()))
+        );
         Ok(())
     }
 }
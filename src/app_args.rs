@@ -1,8 +1,144 @@
-use clap::Parser;
+use std::{path::PathBuf, time::Duration};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::rules::parse_duration;
 
 ///Flags we can pass
 #[derive(Parser, Debug)]
-struct AppArgs {
+pub struct AppArgs {
     #[arg(short, long)]
-    verbose: bool,
+    pub verbose: bool,
+    ///Subscribe to kernel process events to catch short-lived processes (needs CAP_NET_ADMIN)
+    #[arg(long)]
+    pub netlink: bool,
+    ///File of alert rules, one per line, e.g. `cpu > 90 for 30s => highlight; toast`
+    #[arg(long, value_name = "FILE")]
+    pub rules: Option<PathBuf>,
+    ///How long newly started processes stay highlighted, 0 to turn it off
+    #[arg(long, value_name = "DURATION", default_value = "10s", value_parser = parse_duration)]
+    pub new_highlight: Duration,
+    ///How long exited processes stay in the table as dimmed rows, 0 to turn it off
+    #[arg(long, value_name = "DURATION", default_value = "5s", value_parser = parse_duration)]
+    pub ghost: Duration,
+    ///Show the processes of a `rustop agent` instead, "host:port" or "unix:/path".
+    ///Several agents, comma separated or repeated, start with an overview of the hosts
+    #[arg(long, value_name = "ENDPOINT", value_delimiter = ',')]
+    pub connect: Vec<String>,
+    ///Shared secret between agent and client, needed for signals and renice on remote hosts.
+    ///It is sent in cleartext over TCP, tunnel connections that leave the host
+    #[arg(long, env = "RUSTOP_TOKEN", hide_env_values = true, global = true)]
+    pub token: Option<String>,
+    #[command(subcommand)]
+    pub mode: Option<Mode>,
+}
+
+///Ways to run other than the terminal ui
+#[derive(Subcommand, Debug)]
+pub enum Mode {
+    ///Serve snapshots as JSON over HTTP, with a live web dashboard at /
+    Serve {
+        ///Address to listen on, keep it local unless the network is trusted
+        #[arg(long, default_value = "127.0.0.1:8686")]
+        listen: String,
+        ///How often to take a snapshot
+        #[arg(long, value_name = "DURATION", default_value = "1s", value_parser = parse_duration)]
+        interval: Duration,
+    },
+    ///Stream snapshots to `rustop --connect` clients, over TCP or a Unix socket
+    ///
+    ///TCP is cleartext, the token and signals included, reach agents on other hosts through
+    ///an SSH tunnel or a VPN.
+    Agent {
+        ///"host:port", or "unix:/path" for a Unix socket. Listen on all interfaces only with a
+        ///token, and only on a network you trust as TCP is not encrypted
+        #[arg(long, default_value = "127.0.0.1:8687")]
+        listen: String,
+        ///How often to take a snapshot
+        #[arg(long, value_name = "DURATION", default_value = "1s", value_parser = parse_duration)]
+        interval: Duration,
+    },
+    ///Keep a history of processes and system numbers in a SQLite database, until killed
+    Record {
+        ///Database to write, ~/.local/share/rustop/history.db by default
+        #[arg(long, value_name = "FILE")]
+        db: Option<PathBuf>,
+        ///How often to take a sample
+        #[arg(long, value_name = "DURATION", default_value = "1m", value_parser = parse_duration)]
+        interval: Duration,
+        ///How long to keep samples at all
+        #[arg(long, value_name = "DURATION", default_value = "30d", value_parser = parse_duration)]
+        retention: Duration,
+        ///How long to keep samples as taken, older ones are merged
+        #[arg(long, value_name = "DURATION", default_value = "1d", value_parser = parse_duration)]
+        downsample_after: Duration,
+        ///How much time a merged sample covers
+        #[arg(long, value_name = "DURATION", default_value = "10m", value_parser = parse_duration)]
+        downsample_to: Duration,
+    },
+    ///Ask the database `rustop record` keeps what happened
+    History {
+        ///Database to read, ~/.local/share/rustop/history.db by default
+        #[arg(long, value_name = "FILE")]
+        db: Option<PathBuf>,
+        #[command(subcommand)]
+        query: HistoryQuery,
+    },
+}
+
+///What to ask the history
+#[derive(Subcommand, Debug)]
+pub enum HistoryQuery {
+    ///Processes that used the most cpu, or memory, e.g. `top --from 02:00 --to 03:00`
+    Top {
+        ///What to rank the processes by
+        #[arg(long, value_enum, default_value = "cpu")]
+        by: HistorySort,
+        ///How many processes to show
+        #[arg(long, default_value = "10")]
+        limit: usize,
+        #[command(flatten)]
+        options: QueryOptions,
+    },
+    ///Cpu and memory of one pid over time, e.g. `process 1234 --from 1d`
+    Process {
+        pid: u64,
+        #[command(flatten)]
+        options: QueryOptions,
+    },
+    ///Cpu, memory and load of the machine over time
+    System {
+        #[command(flatten)]
+        options: QueryOptions,
+    },
+}
+
+///Time window and output of a history query
+#[derive(Args, Debug)]
+pub struct QueryOptions {
+    ///Start, a time like "02:00", a date like "2025-07-01 02:00", or how long ago like "1d"
+    #[arg(long, value_name = "TIME", default_value = "1h")]
+    pub from: String,
+    ///End, in the same forms as --from, now by default
+    #[arg(long, value_name = "TIME")]
+    pub to: Option<String>,
+    ///How to print the result
+    #[arg(long, value_enum, default_value = "table")]
+    pub format: OutputFormat,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum HistorySort {
+    ///Cpu time used in the window
+    Cpu,
+    ///Largest resident memory in the window
+    Memory,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    ///Aligned columns
+    Table,
+    ///Comma separated, with a header line
+    Csv,
 }
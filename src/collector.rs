@@ -0,0 +1,108 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, SyncSender, TrySendError, sync_channel},
+    },
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+
+use crate::{app::App, system::Snapshot};
+
+///Takes a snapshot every refresh on a thread of its own, for whoever serves them
+#[derive(Default)]
+pub struct Collector {
+    ///The snapshot and its JSON, serialized once for every client
+    latest: Mutex<Option<(Arc<Snapshot>, Arc<String>)>>,
+    ///At most one snapshot waits for each, a slow client skips the ones it can't keep up with
+    subscribers: Mutex<Vec<SyncSender<Arc<String>>>>,
+}
+
+impl Collector {
+    ///Start collecting every `interval`, exiting the process if reading /proc fails
+    pub fn start(interval: Duration) -> Arc<Self> {
+        let collector = Arc::new(Self::default());
+        let shared = collector.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = shared.collect(interval) {
+                eprintln!("Collection stopped: {e}");
+                std::process::exit(1);
+            }
+        });
+        collector
+    }
+
+    fn collect(&self, interval: Duration) -> Result<()> {
+        let mut app = App::new();
+        //clients may show the security column
+        app.read_labels = true;
+        loop {
+            //the app refreshes at most every 250ms, so sleep first
+            std::thread::sleep(interval);
+            app.update_processes()?;
+            self.publish(Snapshot::take(
+                &app.processes,
+                app.pressure.current.clone(),
+            )?)?;
+        }
+    }
+
+    pub fn publish(&self, snapshot: Snapshot) -> Result<()> {
+        let json = Arc::new(serde_json::to_string(&snapshot)?);
+        *self
+            .latest
+            .lock()
+            .map_err(|_| anyhow!("Snapshot lock poisoned"))? =
+            Some((Arc::new(snapshot), json.clone()));
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            //a client that went away is dropped, one still busy with the last snapshot skips this one
+            subscribers.retain(|subscriber| {
+                !matches!(
+                    subscriber.try_send(json.clone()),
+                    Err(TrySendError::Disconnected(_))
+                )
+            });
+        }
+        Ok(())
+    }
+
+    pub fn latest(&self) -> Option<(Arc<Snapshot>, Arc<String>)> {
+        self.latest.lock().ok()?.clone()
+    }
+
+    ///JSON of snapshots from now on, starting with the latest if there is one.
+    ///Snapshots taken while the previous one is still unread are skipped.
+    pub fn subscribe(&self) -> Result<Receiver<Arc<String>>> {
+        let (sender, receiver) = sync_channel(1);
+        if let Some((_, json)) = self.latest() {
+            sender.send(json)?;
+        }
+        self.subscribers
+            .lock()
+            .map_err(|_| anyhow!("Subscriber lock poisoned"))?
+            .push(sender);
+        Ok(receiver)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_slow_subscribers_skip() -> Result<()> {
+        let collector = Collector::default();
+        let receiver = collector.subscribe()?;
+        for _ in 0..3 {
+            collector.publish(Snapshot::take(&[], None)?)?;
+        }
+        assert!(receiver.try_recv().is_ok());
+        assert!(receiver.try_recv().is_err());
+
+        drop(receiver);
+        collector.publish(Snapshot::take(&[], None)?)?;
+        assert!(collector.subscribers.lock().unwrap().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,337 @@
+use crossterm::event::KeyCode;
+use nix::sys::signal::Signal;
+
+use crate::{model::SortBy, signals::COMMON_SIGNALS};
+
+///Everything that can be done from the process table, by key or from the command palette
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Quit,
+    Help,
+    ///Sort on a column, again to reverse it
+    Sort(SortBy),
+    ReverseSort,
+    SortPrevious,
+    SortNext,
+    Filter,
+    ClearFilter,
+    JumpToPid,
+    Parent,
+    FirstChild,
+    ///Open the detail view of a process, or expand a group
+    OpenDetail,
+    ToggleGrouping,
+    ToggleEvents,
+    ToggleNetwork,
+    ToggleDisks,
+    ToggleSensors,
+    ToggleSecurity,
+    ToggleActivity,
+    ToggleOom,
+    ToggleFollow,
+    ///Tag or untag the selected process, every member of a selected group
+    Tag,
+    TagVisible,
+    ///Tag the selected process and everything it started
+    TagSubtree,
+    Untag,
+    EditAffinity,
+    EditOomScoreAdj,
+    EditNice,
+    ///Ask to send a signal to the targets, starting with this one
+    Signal(Signal),
+}
+
+///Keys of the process table and the command each runs, letters work in either case
+pub const TABLE_KEYS: [(KeyCode, Command); 32] = [
+    (KeyCode::Char('q'), Command::Quit),
+    (KeyCode::Esc, Command::Quit),
+    (KeyCode::Char('?'), Command::Help),
+    (KeyCode::Char('c'), Command::Sort(SortBy::Cpu)),
+    (KeyCode::Char('m'), Command::Sort(SortBy::Memory)),
+    (KeyCode::Char('p'), Command::Sort(SortBy::Pid)),
+    (KeyCode::Char('n'), Command::Sort(SortBy::Name)),
+    (KeyCode::Char('o'), Command::Sort(SortBy::Command)),
+    (KeyCode::Char('<'), Command::SortPrevious),
+    (KeyCode::Char('>'), Command::SortNext),
+    (KeyCode::Char('/'), Command::Filter),
+    (KeyCode::Char(':'), Command::JumpToPid),
+    (KeyCode::Char('h'), Command::Parent),
+    (KeyCode::Char('l'), Command::FirstChild),
+    (KeyCode::Enter, Command::OpenDetail),
+    (KeyCode::Char('a'), Command::ToggleGrouping),
+    (KeyCode::Char('e'), Command::ToggleEvents),
+    (KeyCode::Char('i'), Command::ToggleNetwork),
+    (KeyCode::Char('d'), Command::ToggleDisks),
+    (KeyCode::Char('t'), Command::ToggleSensors),
+    (KeyCode::Char('s'), Command::ToggleSecurity),
+    (KeyCode::Char('v'), Command::ToggleActivity),
+    (KeyCode::Char('y'), Command::ToggleOom),
+    (KeyCode::Char('f'), Command::ToggleFollow),
+    (KeyCode::Char(' '), Command::Tag),
+    (KeyCode::Char('*'), Command::TagVisible),
+    (KeyCode::Char('+'), Command::TagSubtree),
+    (KeyCode::Char('u'), Command::Untag),
+    (KeyCode::Char('b'), Command::EditAffinity),
+    (KeyCode::Char('z'), Command::EditOomScoreAdj),
+    (KeyCode::Char('r'), Command::EditNice),
+    (KeyCode::Char('x'), Command::Signal(Signal::SIGTERM)),
+];
+
+///Keys of the process table that move the selection rather than run a command
+pub const TABLE_MOTIONS: [(&str, &str); 5] = [
+    ("j k ↓ ↑", "Move down or up, a count first repeats it (5j)"),
+    ("PgDn PgUp", "Move a page down or up"),
+    ("Home gg", "First row, Ngg goes to row N"),
+    ("End G", "Last row, NG goes to row N"),
+    ("[ ]", "Scroll the event log"),
+];
+
+///Keys of the detail view
+pub const DETAIL_KEYS: [(&str, &str); 5] = [
+    ("Tab → l", "Next tab"),
+    ("S-Tab ← h", "Previous tab"),
+    ("j k ↓ ↑", "Scroll, or pick a limit on the Limits tab"),
+    ("r", "Reload"),
+    ("e", "Edit the selected limit"),
+];
+
+///Keys of the host overview
+pub const OVERVIEW_KEYS: [(&str, &str); 5] = [
+    ("j k ↓ ↑", "Pick a host, or all of them at the bottom"),
+    ("Enter", "Process table of the host, q goes back"),
+    ("c m l", "Busiest cpu, memory or load first, again to undo"),
+    ("n", "Sort by name, again to undo"),
+    ("q Esc", "Quit"),
+];
+
+///Keys that work everywhere except in a prompt
+pub const GLOBAL_KEYS: [(&str, &str); 3] = [
+    ("?", "This help"),
+    ("Ctrl-P", "Command palette"),
+    ("q Esc", "Back, quit from the table"),
+];
+
+pub fn key_name(key: &KeyCode) -> String {
+    match key {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+impl Command {
+    ///The command a key runs in the process table
+    pub fn for_key(key: KeyCode) -> Option<Command> {
+        let key = match key {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            other => other,
+        };
+        TABLE_KEYS
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, command)| command.clone())
+    }
+
+    ///Keys that run this command, e.g. "q Esc"
+    pub fn keys(&self) -> String {
+        TABLE_KEYS
+            .iter()
+            .filter(|(_, command)| command == self)
+            .map(|(key, _)| key_name(key))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Command::Quit => "Quit".to_string(),
+            Command::Help => "Show the keys of this view".to_string(),
+            Command::Sort(sort) => format!("Sort by {}", sort.title()),
+            Command::ReverseSort => "Reverse the sort order".to_string(),
+            Command::SortPrevious => "Sort by the column to the left".to_string(),
+            Command::SortNext => "Sort by the column to the right".to_string(),
+            Command::Filter => "Filter processes".to_string(),
+            Command::ClearFilter => "Clear the filter".to_string(),
+            Command::JumpToPid => "Jump to a pid".to_string(),
+            Command::Parent => "Jump to the parent".to_string(),
+            Command::FirstChild => "Jump to the first child".to_string(),
+            Command::OpenDetail => "Open details, or expand a group".to_string(),
+            Command::ToggleGrouping => "Aggregate by command, executable or not at all".to_string(),
+            Command::ToggleEvents => "Toggle the event log".to_string(),
+            Command::ToggleNetwork => "Toggle network interfaces".to_string(),
+            Command::ToggleDisks => "Toggle disks, then all block devices".to_string(),
+            Command::ToggleSensors => "Toggle temperature sensors".to_string(),
+            Command::ToggleSecurity => "Toggle the security column".to_string(),
+            Command::ToggleActivity => {
+                "Toggle the page fault and context switch columns".to_string()
+            }
+            Command::ToggleOom => "Toggle the OOM score columns".to_string(),
+            Command::ToggleFollow => "Follow the selected process, or stop".to_string(),
+            Command::Tag => "Tag or untag the selected process".to_string(),
+            Command::TagVisible => "Tag every process shown".to_string(),
+            Command::TagSubtree => "Tag the selected process and its descendants".to_string(),
+            Command::Untag => "Untag everything".to_string(),
+            Command::EditAffinity => "Set cpu affinity of the tagged or selected".to_string(),
+            Command::EditOomScoreAdj => "Set oom_score_adj".to_string(),
+            Command::EditNice => "Renice the tagged or selected".to_string(),
+            Command::Signal(signal) => {
+                format!("Send {} to the tagged or selected", signal.as_str())
+            }
+        }
+    }
+
+    ///Commands that read or change what only this host has
+    pub fn is_local_only(&self) -> bool {
+        matches!(
+            self,
+            Command::ToggleNetwork
+                | Command::ToggleDisks
+                | Command::ToggleSensors
+                | Command::EditAffinity
+                | Command::EditOomScoreAdj
+        )
+    }
+
+    ///Every command, in the order the palette lists them before anything is typed
+    pub fn all() -> Vec<Command> {
+        let mut commands: Vec<Command> = SortBy::ALL.iter().cloned().map(Command::Sort).collect();
+        commands.extend([
+            Command::ReverseSort,
+            Command::SortPrevious,
+            Command::SortNext,
+            Command::Filter,
+            Command::ClearFilter,
+            Command::JumpToPid,
+            Command::Parent,
+            Command::FirstChild,
+            Command::OpenDetail,
+            Command::ToggleGrouping,
+            Command::ToggleEvents,
+            Command::ToggleNetwork,
+            Command::ToggleDisks,
+            Command::ToggleSensors,
+            Command::ToggleSecurity,
+            Command::ToggleActivity,
+            Command::ToggleOom,
+            Command::ToggleFollow,
+            Command::Tag,
+            Command::TagVisible,
+            Command::TagSubtree,
+            Command::Untag,
+            Command::EditAffinity,
+            Command::EditOomScoreAdj,
+            Command::EditNice,
+        ]);
+        commands.extend(COMMON_SIGNALS.into_iter().map(Command::Signal));
+        commands.extend([Command::Help, Command::Quit]);
+        commands
+    }
+}
+
+///Score how well `query` matches `text`. Every character of the query has to appear in
+///order, those at the start of a word or right after the previous match score more.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last_match: Option<usize> = None;
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = pos + text[pos..].iter().position(|&c| c == q)?;
+        score += 1;
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 8;
+        }
+        if last_match.is_some_and(|last| last + 1 == found) {
+            score += 5;
+        }
+        //a long way in is a weaker match
+        score -= (found - pos).min(5) as i64;
+        last_match = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+///Command palette, the commands matching what was typed so far
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Palette {
+    pub query: String,
+    ///Index into `matches()`
+    pub selected: usize,
+}
+
+impl Palette {
+    ///Commands matching the query, best first
+    pub fn matches(&self) -> Vec<Command> {
+        let mut scored: Vec<(i64, Command)> = Command::all()
+            .into_iter()
+            .filter_map(|command| Some((fuzzy_score(&self.query, &command.label())?, command)))
+            .collect();
+        //stable, ties keep the palette order
+        scored.sort_by_key(|(score, _)| -score);
+        scored.into_iter().map(|(_, command)| command).collect()
+    }
+
+    pub fn selected_command(&self) -> Option<Command> {
+        self.matches().into_iter().nth(self.selected)
+    }
+
+    pub fn next(&mut self) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected + 1) % count;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected + count - 1) % count;
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_palette() {
+        assert!(fuzzy_score("xyz", "Sort by cpu").is_none());
+        assert!(fuzzy_score("sbc", "Sort by cpu") > fuzzy_score("sbc", "Sort the columns"));
+
+        let mut palette = Palette::default();
+        "kill".chars().for_each(|c| palette.push(c));
+        assert_eq!(
+            palette.selected_command(),
+            Some(Command::Signal(Signal::SIGKILL))
+        );
+        palette.push('z');
+        assert!(palette.matches().is_empty());
+        palette.query.clear();
+        "mem".chars().for_each(|c| palette.push(c));
+        assert_eq!(
+            palette.selected_command(),
+            Some(Command::Sort(SortBy::Memory))
+        );
+
+        assert_eq!(
+            Command::for_key(KeyCode::Char('M')),
+            Some(Command::Sort(SortBy::Memory))
+        );
+        assert_eq!(Command::Quit.keys(), "q Esc");
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{
+    kstack::{ThreadStack, read_thread_stacks},
+    limits::{Limit, LimitUsage, read_limits},
+    memmap::{MappingSummary, MemoryRegion, read_memory_map, summarize},
+    model::{ProcessInfo, Target},
+    security::{SecurityContext, describe_capabilities, read_security},
+};
+
+///Tabs of the process detail view
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DetailTab {
+    ///Memory aggregated per mapping
+    Mappings,
+    ///Every region of the memory map
+    Regions,
+    ///Wait channel and kernel stack of each thread
+    Kernel,
+    ///Resource limits next to what the process uses of them
+    Limits,
+    ///Credentials, capabilities, seccomp and LSM label
+    Security,
+}
+
+impl DetailTab {
+    pub const ALL: [DetailTab; 5] = [
+        DetailTab::Mappings,
+        DetailTab::Regions,
+        DetailTab::Kernel,
+        DetailTab::Limits,
+        DetailTab::Security,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            DetailTab::Mappings => "Mappings",
+            DetailTab::Regions => "Regions",
+            DetailTab::Kernel => "Kernel",
+            DetailTab::Limits => "Limits",
+            DetailTab::Security => "Security",
+        }
+    }
+
+    fn index(&self) -> usize {
+        Self::ALL.iter().position(|t| t == self).unwrap_or(0)
+    }
+
+    pub fn next(&self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    pub fn previous(&self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+///Everything we show about a single process, read when the view is opened or reloaded
+#[derive(Debug, Clone)]
+pub struct ProcessDetail {
+    ///The process shown, limits are only changed while it is still the one with the pid
+    pub target: Target,
+    pub name: String,
+    pub tab: DetailTab,
+    ///First visible line of the current tab
+    pub scroll: usize,
+    ///Regions from smaps (or maps), or why we couldn't read them
+    pub regions: Result<Vec<MemoryRegion>, String>,
+    pub mappings: Vec<MappingSummary>,
+    pub threads: Vec<ThreadStack>,
+    pub limits: Result<Vec<Limit>, String>,
+    pub limit_usage: LimitUsage,
+    pub security: Result<SecurityContext, String>,
+}
+
+impl ProcessDetail {
+    pub fn load(process: &ProcessInfo) -> Self {
+        let mut detail = Self {
+            target: Target::of(process),
+            name: process.name.clone(),
+            tab: DetailTab::Mappings,
+            scroll: 0,
+            regions: Ok(Vec::new()),
+            mappings: Vec::new(),
+            threads: Vec::new(),
+            limits: Ok(Vec::new()),
+            limit_usage: LimitUsage::default(),
+            security: Ok(SecurityContext::default()),
+        };
+        detail.reload();
+        detail
+    }
+
+    pub fn reload(&mut self) {
+        self.regions = read_memory_map(self.target.pid).map_err(|e| e.to_string());
+        self.mappings = match &self.regions {
+            Ok(regions) => summarize(regions),
+            Err(_) => Vec::new(),
+        };
+        self.threads = read_thread_stacks(self.target.pid);
+        self.limits = read_limits(self.target.pid).map_err(|e| e.to_string());
+        self.limit_usage = LimitUsage::read(self.target.pid);
+        self.security = read_security(self.target.pid).map_err(|e| e.to_string());
+    }
+
+    ///Whether smaps was readable, so we have per region usage
+    pub fn has_usage(&self) -> bool {
+        self.regions
+            .as_ref()
+            .is_ok_and(|regions| regions.iter().any(|r| r.rss_kb.is_some()))
+    }
+
+    ///How many lines the current tab has, to bound scrolling
+    pub fn line_count(&self) -> usize {
+        match self.tab {
+            DetailTab::Mappings => self.mappings.len(),
+            DetailTab::Regions => self.regions.as_ref().map(|r| r.len()).unwrap_or(0),
+            DetailTab::Kernel => self.kernel_lines().len(),
+            DetailTab::Limits => self.limits.as_ref().map(|l| l.len()).unwrap_or(0),
+            DetailTab::Security => self.security_lines().len(),
+        }
+    }
+
+    ///Label and value for every line of the security tab
+    pub fn security_lines(&self) -> Vec<(String, String)> {
+        let context = match &self.security {
+            Ok(context) => context,
+            Err(e) => return vec![("Error".to_string(), e.clone())],
+        };
+        let ids = |ids: [u32; 4], name: fn(u32) -> Option<String>| {
+            ids.map(|id| match name(id) {
+                Some(name) => format!("{id} ({name})"),
+                None => id.to_string(),
+            })
+            .join(" / ")
+        };
+        let user_name =
+            |uid| users::get_user_by_uid(uid).map(|u| u.name().to_string_lossy().to_string());
+        let group_name =
+            |gid| users::get_group_by_gid(gid).map(|g| g.name().to_string_lossy().to_string());
+        let caps = &context.capabilities;
+        vec![
+            (
+                "UIDs r/e/s/fs".to_string(),
+                ids(context.uids.all(), user_name),
+            ),
+            (
+                "GIDs r/e/s/fs".to_string(),
+                ids(context.gids.all(), group_name),
+            ),
+            (
+                "Seccomp".to_string(),
+                context.seccomp.description().to_string(),
+            ),
+            (
+                "NoNewPrivs".to_string(),
+                if context.no_new_privs { "yes" } else { "no" }.to_string(),
+            ),
+            (
+                "LSM label".to_string(),
+                context.label.clone().unwrap_or_else(|| "-".to_string()),
+            ),
+            ("CapEff".to_string(), describe_capabilities(caps.effective)),
+            ("CapPrm".to_string(), describe_capabilities(caps.permitted)),
+            (
+                "CapInh".to_string(),
+                describe_capabilities(caps.inheritable),
+            ),
+            ("CapBnd".to_string(), describe_capabilities(caps.bounding)),
+            ("CapAmb".to_string(), describe_capabilities(caps.ambient)),
+        ]
+    }
+
+    ///On the limits tab the scroll position doubles as the selected limit
+    pub fn selected_limit(&self) -> Option<&Limit> {
+        self.limits.as_ref().ok()?.get(self.scroll)
+    }
+
+    ///One heading per thread followed by its stack frames
+    pub fn kernel_lines(&self) -> Vec<(bool, String)> {
+        let mut lines = Vec::new();
+        for thread in &self.threads {
+            let wchan = if thread.wchan.is_empty() {
+                "-"
+            } else {
+                thread.wchan.as_str()
+            };
+            lines.push((
+                true,
+                format!(
+                    "{} {} [{}] wchan: {wchan}",
+                    thread.tid,
+                    thread.name,
+                    thread.state.description()
+                ),
+            ));
+            match &thread.stack {
+                Ok(frames) => lines.extend(frames.iter().map(|f| (false, format!("    {f}")))),
+                Err(e) => lines.push((false, format!("    stack unavailable: {e}"))),
+            }
+        }
+        lines
+    }
+
+    pub fn scroll_down(&mut self) {
+        if self.scroll + 1 < self.line_count() {
+            self.scroll += 1;
+        }
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    pub fn switch_tab(&mut self, tab: DetailTab) {
+        self.tab = tab;
+        self.scroll = 0;
+    }
+}
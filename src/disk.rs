@@ -0,0 +1,214 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    time::Instant,
+};
+
+use anyhow::{Result, anyhow};
+
+use crate::model::push_history;
+
+///Sectors in /proc/diskstats are always 512 bytes, whatever the device uses
+const SECTOR_SIZE: u64 = 512;
+
+///Cumulative counters of one block device, as found in `/proc/diskstats`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiskCounters {
+    pub name: String,
+    pub reads: u64,
+    pub sectors_read: u64,
+    pub ms_reading: u64,
+    pub writes: u64,
+    pub sectors_written: u64,
+    pub ms_writing: u64,
+    ///Milliseconds the device had requests in flight
+    pub ms_doing_io: u64,
+}
+
+///Per second rates of one block device
+#[derive(Debug, Clone, Default)]
+pub struct DiskStats {
+    pub name: String,
+    pub read_bytes_per_sec: f64,
+    pub write_bytes_per_sec: f64,
+    pub reads_per_sec: f64,
+    pub writes_per_sec: f64,
+    ///Average milliseconds a completed request took, queueing included
+    pub await_ms: f64,
+    pub utilization_percent: f64,
+    pub utilization_history: VecDeque<u64>,
+}
+
+///Parse the contents of `/proc/diskstats`
+pub fn parse_diskstats(content: &str) -> Result<Vec<DiskCounters>> {
+    let mut disks = Vec::new();
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 14 {
+            return Err(anyhow!(
+                "Insufficient fields in diskstats: got {}, need at least 14",
+                fields.len()
+            ));
+        }
+        let number = |i: usize| fields[i].parse::<u64>();
+        disks.push(DiskCounters {
+            name: fields[2].to_string(),
+            reads: number(3)?,
+            sectors_read: number(5)?,
+            ms_reading: number(6)?,
+            writes: number(7)?,
+            sectors_written: number(9)?,
+            ms_writing: number(10)?,
+            ms_doing_io: number(12)?,
+        });
+    }
+    Ok(disks)
+}
+
+///Loop and ram devices are rarely interesting
+pub fn is_virtual_device(name: &str) -> bool {
+    name.starts_with("loop") || name.starts_with("ram")
+}
+
+///Partitions have a `partition` attribute in sysfs, whole disks don't
+pub fn is_partition(sys_class_block: &Path, name: &str) -> bool {
+    sys_class_block.join(name).join("partition").exists()
+}
+
+///Turns successive `/proc/diskstats` readings into rates
+#[derive(Debug, Default)]
+pub struct DiskMonitor {
+    last: HashMap<String, DiskCounters>,
+    last_sample: Option<Instant>,
+    ///Whole, physical disks only, unless `show_all` is set
+    pub disks: Vec<DiskStats>,
+    pub show_all: bool,
+}
+
+impl DiskMonitor {
+    pub fn update(&mut self) -> Result<()> {
+        let content = std::fs::read_to_string("/proc/diskstats")?;
+        let sys_class_block = Path::new("/sys/class/block");
+        let counters = parse_diskstats(&content)?
+            .into_iter()
+            .filter(|d| {
+                self.show_all
+                    || !(is_virtual_device(&d.name) || is_partition(sys_class_block, &d.name))
+            })
+            .collect();
+        self.record(counters, Instant::now());
+        Ok(())
+    }
+
+    fn record(&mut self, counters: Vec<DiskCounters>, now: Instant) {
+        let elapsed = self
+            .last_sample
+            .map(|last| now.duration_since(last).as_secs_f64())
+            .unwrap_or(0.0);
+        let mut disks = Vec::with_capacity(counters.len());
+        for current in &counters {
+            let mut stats = self
+                .disks
+                .iter()
+                .find(|d| d.name == current.name)
+                .cloned()
+                .unwrap_or_else(|| DiskStats {
+                    name: current.name.clone(),
+                    ..DiskStats::default()
+                });
+            if let Some(previous) = self.last.get(&current.name)
+                && elapsed > 0.0
+            {
+                let delta = |now: u64, before: u64| now.saturating_sub(before) as f64;
+                let requests =
+                    delta(current.reads, previous.reads) + delta(current.writes, previous.writes);
+                let wait_ms = delta(current.ms_reading, previous.ms_reading)
+                    + delta(current.ms_writing, previous.ms_writing);
+                stats.read_bytes_per_sec = delta(current.sectors_read, previous.sectors_read)
+                    * SECTOR_SIZE as f64
+                    / elapsed;
+                stats.write_bytes_per_sec =
+                    delta(current.sectors_written, previous.sectors_written) * SECTOR_SIZE as f64
+                        / elapsed;
+                stats.reads_per_sec = delta(current.reads, previous.reads) / elapsed;
+                stats.writes_per_sec = delta(current.writes, previous.writes) / elapsed;
+                stats.await_ms = if requests > 0.0 {
+                    wait_ms / requests
+                } else {
+                    0.0
+                };
+                stats.utilization_percent =
+                    (delta(current.ms_doing_io, previous.ms_doing_io) / (elapsed * 1000.0) * 100.0)
+                        .min(100.0);
+                push_history(
+                    &mut stats.utilization_history,
+                    stats.utilization_percent as u64,
+                );
+            }
+            disks.push(stats);
+        }
+        disks.sort_by(|a, b| a.name.cmp(&b.name));
+        self.disks = disks;
+        self.last = counters.into_iter().map(|c| (c.name.clone(), c)).collect();
+        self.last_sample = Some(now);
+    }
+
+    ///The device with the highest utilization, if any is doing anything
+    pub fn busiest(&self) -> Option<&DiskStats> {
+        self.disks
+            .iter()
+            .filter(|d| d.utilization_percent > 0.0)
+            .max_by(|a, b| {
+                a.utilization_percent
+                    .partial_cmp(&b.utilization_percent)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    const DISKSTATS: &str = "\
+   7       0 loop0 10 0 80 5 0 0 0 0 0 4 5 0 0 0 0 0 0
+ 253       0 vda 1000 10 8000 500 200 20 1600 300 0 600 800 0 0 0 0 0 0
+ 253       1 vda1 900 10 7000 450 200 20 1600 300 0 550 750 0 0 0 0 0 0
+";
+
+    #[test]
+    pub fn test_parse_diskstats() -> Result<()> {
+        let disks = parse_diskstats(DISKSTATS)?;
+        assert_eq!(disks.len(), 3);
+        assert_eq!(disks[1].name, "vda");
+        assert_eq!(disks[1].sectors_read, 8000);
+        assert_eq!(disks[1].ms_writing, 300);
+        assert_eq!(disks[1].ms_doing_io, 600);
+        assert!(is_virtual_device(&disks[0].name));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_rates() -> Result<()> {
+        let mut monitor = DiskMonitor::default();
+        let start = Instant::now();
+        let mut disks = parse_diskstats(DISKSTATS)?;
+        disks.truncate(2);
+        monitor.record(disks.clone(), start);
+        //one second later: 100 reads of 8 sectors taking 2ms each, busy half the time
+        disks[1].reads += 100;
+        disks[1].sectors_read += 800;
+        disks[1].ms_reading += 200;
+        disks[1].ms_doing_io += 500;
+        monitor.record(disks, start + Duration::from_secs(1));
+        let vda = &monitor.disks[1];
+        assert_eq!(vda.reads_per_sec, 100.0);
+        assert_eq!(vda.read_bytes_per_sec, 800.0 * 512.0);
+        assert_eq!(vda.await_ms, 2.0);
+        assert_eq!(vda.utilization_percent, 50.0);
+        assert_eq!(monitor.busiest().map(|d| d.name.as_str()), Some("vda"));
+        Ok(())
+    }
+}
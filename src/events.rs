@@ -0,0 +1,204 @@
+use std::collections::{HashSet, VecDeque};
+
+use chrono::{DateTime, Local};
+
+use crate::model::{ProcessInfo, short_command};
+
+///How many events we hold on to before dropping the oldest
+const EVENT_LOG_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LifecycleKind {
+    Started,
+    ///Only reported by the proc connector
+    Exec,
+    Exited,
+}
+
+///A process appearing or disappearing between two snapshots
+#[derive(Debug, Clone)]
+pub struct LifecycleEvent {
+    pub kind: LifecycleKind,
+    pub timestamp: DateTime<Local>,
+    pub pid: u64,
+    ///Clock ticks after boot, tells the process apart from later ones with the same pid
+    pub start_time: Option<u64>,
+    pub ppid: u64,
+    ///Name of the parent, if it was in the same snapshot
+    pub parent: String,
+    pub name: String,
+    pub command: String,
+    ///How long the process ran, only known for exits
+    pub lifetime_secs: Option<f64>,
+    ///Last seen usage, only known for exits
+    pub cpu_percent: f64,
+    pub memory_kb: u64,
+    ///Total cpu time used over the whole lifetime, only known for exits
+    pub cpu_secs: Option<f64>,
+    ///Exit code and terminating signal, only known from the proc connector
+    pub exit_code: Option<i32>,
+    pub exit_signal: Option<i32>,
+}
+
+impl LifecycleEvent {
+    pub fn blank(kind: LifecycleKind, pid: u64, name: &str, command: &str) -> Self {
+        Self {
+            kind,
+            timestamp: Local::now(),
+            pid,
+            start_time: None,
+            ppid: 0,
+            parent: String::new(),
+            name: name.to_string(),
+            command: command.to_string(),
+            lifetime_secs: None,
+            cpu_percent: 0.0,
+            memory_kb: 0,
+            cpu_secs: None,
+            exit_code: None,
+            exit_signal: None,
+        }
+    }
+
+    fn new(kind: LifecycleKind, process: &ProcessInfo, parent: String) -> Self {
+        Self {
+            start_time: Some(process.start_time),
+            ppid: process.ppid,
+            parent,
+            ..Self::blank(kind, process.pid, &process.name, &process.command)
+        }
+    }
+
+    ///Short command used for per-command accounting
+    pub fn short_command(&self) -> &str {
+        short_command(&self.command, &self.name)
+    }
+}
+
+///Cpu used by all exited processes of one command
+#[derive(Debug, Clone, Default)]
+pub struct CommandCpu {
+    pub runs: u64,
+    pub cpu_secs: f64,
+}
+
+///Bounded, scrollable history of lifecycle events
+#[derive(Debug, Default)]
+pub struct EventLog {
+    pub events: VecDeque<LifecycleEvent>,
+    ///How many events back from the newest we are scrolled
+    pub scroll: usize,
+}
+
+impl EventLog {
+    pub fn push(&mut self, event: LifecycleEvent) {
+        if self.events.len() >= EVENT_LOG_CAPACITY {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+        //stay on the same event when scrolled back
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.events.len() - 1);
+        }
+    }
+
+    pub fn scroll_up(&mut self) {
+        if self.scroll + 1 < self.events.len() {
+            self.scroll += 1;
+        }
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    ///Events from newest to oldest, starting at the scroll position
+    pub fn visible(&self) -> impl Iterator<Item = &LifecycleEvent> {
+        self.events.iter().rev().skip(self.scroll)
+    }
+}
+
+///Compare two snapshots and report which processes started and exited.
+///Processes are matched on pid and start time, so a reused pid shows up as an exit and a start.
+pub fn diff_snapshots(old: &[ProcessInfo], new: &[ProcessInfo]) -> Vec<LifecycleEvent> {
+    //pids of different hosts are different processes
+    let key = |p: &ProcessInfo| (p.host.clone(), p.pid, p.start_time);
+    let old_keys: HashSet<_> = old.iter().map(key).collect();
+    let new_keys: HashSet<_> = new.iter().map(key).collect();
+    let now = Local::now().timestamp() as f64;
+
+    let mut events = Vec::new();
+    for process in old {
+        if new_keys.contains(&key(process)) {
+            continue;
+        }
+        let mut event =
+            LifecycleEvent::new(LifecycleKind::Exited, process, parent_name(process, old));
+        event.lifetime_secs = Some((now - process.start_epoch_secs()).max(0.0));
+        event.cpu_percent = process.cpu_percent;
+        event.memory_kb = process.memory_kb;
+        event.cpu_secs = Some(process.cpu_time_secs());
+        events.push(event);
+    }
+    for process in new {
+        if old_keys.contains(&key(process)) {
+            continue;
+        }
+        events.push(LifecycleEvent::new(
+            LifecycleKind::Started,
+            process,
+            parent_name(process, new),
+        ));
+    }
+    events
+}
+
+fn parent_name(process: &ProcessInfo, snapshot: &[ProcessInfo]) -> String {
+    snapshot
+        .iter()
+        .find(|p| p.pid == process.ppid && p.host == process.host)
+        .map(|p| p.name.clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn process(pid: u64, ppid: u64, name: &str, start_time: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            ppid,
+            name: name.to_string(),
+            start_time,
+            ..ProcessInfo::new()
+        }
+    }
+
+    #[test]
+    pub fn test_diff_snapshots_detects_pid_reuse() {
+        let old = vec![process(1, 0, "init", 1), process(42, 1, "worker", 100)];
+        let new = vec![process(1, 0, "init", 1), process(42, 1, "worker", 200)];
+        let events = diff_snapshots(&old, &new);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, LifecycleKind::Exited);
+        assert_eq!(events[0].parent, "init");
+        assert!(events[0].lifetime_secs.is_some());
+        assert_eq!(events[1].kind, LifecycleKind::Started);
+        assert_eq!(events[1].pid, 42);
+    }
+
+    #[test]
+    pub fn test_event_log_is_bounded() {
+        let mut log = EventLog::default();
+        let template = process(1, 0, "init", 1);
+        for _ in 0..EVENT_LOG_CAPACITY + 5 {
+            log.push(LifecycleEvent::new(
+                LifecycleKind::Started,
+                &template,
+                String::new(),
+            ));
+        }
+        assert_eq!(log.events.len(), EVENT_LOG_CAPACITY);
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::HashMap,
+};
+
+use crate::model::{GroupBy, ProcessGroup, ProcessInfo, SortBy};
+
+///Collapse processes that share a command (or executable) into groups.
+///Groups keep the order in which their first member appears in `processes`,
+///and members keep the order of `processes`.
+pub fn group_processes(processes: &[ProcessInfo], group_by: &GroupBy) -> Vec<ProcessGroup> {
+    let mut groups: Vec<ProcessGroup> = Vec::new();
+    let mut group_index: HashMap<String, usize> = HashMap::new();
+
+    for (i, process) in processes.iter().enumerate() {
+        let key = group_key(process, group_by);
+        let idx = *group_index.entry(key.clone()).or_insert_with(|| {
+            groups.push(ProcessGroup::new(key));
+            groups.len() - 1
+        });
+        let group = &mut groups[idx];
+        group.members.push(i);
+        group.cpu_percent += process.cpu_percent;
+        group.memory_kb += process.memory_kb;
+        group.activity_rates.add(&process.activity_rates);
+    }
+
+    groups
+}
+
+///The value processes are grouped on
+pub fn group_key(process: &ProcessInfo, group_by: &GroupBy) -> String {
+    match group_by {
+        GroupBy::Command => process.short_command().to_string(),
+        //we cannot read the exe link of other users' processes, fall back on the command
+        GroupBy::Executable if process.exe.is_empty() => process.short_command().to_string(),
+        GroupBy::Executable => process.exe.clone(),
+    }
+}
+
+///Sort groups the same way the process table is sorted, using the aggregated values
+pub fn sort_groups(
+    groups: &mut [ProcessGroup],
+    processes: &[ProcessInfo],
+    sort_by: &Option<SortBy>,
+    reverse_sort: bool,
+) {
+    let lowest_pid = |group: &ProcessGroup| {
+        group
+            .members
+            .iter()
+            .map(|&i| processes[i].pid)
+            .min()
+            .unwrap_or(0)
+    };
+    let earliest_start = |group: &ProcessGroup| {
+        group
+            .members
+            .iter()
+            .map(|&i| processes[i].start_time)
+            .min()
+            .unwrap_or(0)
+    };
+    match sort_by {
+        Some(SortBy::Cpu) => groups.sort_by(|a, b| {
+            a.cpu_percent
+                .partial_cmp(&b.cpu_percent)
+                .unwrap_or(Ordering::Equal)
+        }),
+        Some(SortBy::Memory) => groups.sort_by_key(|g| g.memory_kb),
+        Some(SortBy::Pid) => groups.sort_by_key(|g| lowest_pid(g)),
+        Some(SortBy::Name) | Some(SortBy::Command) => groups.sort_by(|a, b| a.key.cmp(&b.key)),
+        //a group started when its first member did
+        Some(SortBy::Start) => groups.sort_by_key(|g| earliest_start(g)),
+        Some(SortBy::Age) => groups.sort_by_key(|g| Reverse(earliest_start(g))),
+        Some(SortBy::Time) => groups.sort_by_key(|g| {
+            g.members
+                .iter()
+                .map(|&i| processes[i].cpu_time_total)
+                .sum::<u64>()
+        }),
+        Some(SortBy::OomScore) => groups.sort_by_key(|g| g.max_member(processes, |p| p.oom_score)),
+        Some(SortBy::OomScoreAdj) => {
+            groups.sort_by_key(|g| g.max_member(processes, |p| p.oom_score_adj))
+        }
+        Some(
+            sort @ (SortBy::MinorFaults
+            | SortBy::MajorFaults
+            | SortBy::VoluntarySwitches
+            | SortBy::InvoluntarySwitches),
+        ) => groups.sort_by(|a, b| {
+            sort.activity_rate(&a.activity_rates)
+                .partial_cmp(&sort.activity_rate(&b.activity_rates))
+                .unwrap_or(Ordering::Equal)
+        }),
+        None => {}
+    }
+    if reverse_sort {
+        groups.reverse();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn process(pid: u64, command: &str, cpu_percent: f64, memory_kb: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            command: command.to_string(),
+            cpu_percent,
+            memory_kb,
+            ..ProcessInfo::new()
+        }
+    }
+
+    #[test]
+    pub fn test_group_processes_sums_members() {
+        let processes = vec![
+            process(1, "/usr/bin/postgres -D /data", 1.5, 100),
+            process(2, "/usr/bin/bash", 0.0, 10),
+            process(3, "/usr/bin/postgres -c writer", 2.5, 50),
+        ];
+        let groups = group_processes(&processes, &GroupBy::Command);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].key, "postgres");
+        assert_eq!(groups[0].members, vec![0, 2]);
+        assert_eq!(groups[0].cpu_percent, 4.0);
+        assert_eq!(groups[0].memory_kb, 150);
+        assert_eq!(groups[1].count(), 1);
+    }
+
+    #[test]
+    pub fn test_group_by_executable_falls_back_to_command() {
+        let mut python = process(1, "python3 worker.py", 0.0, 0);
+        python.exe = "/usr/bin/python3.12".to_string();
+        let hidden = process(2, "python3 other.py", 0.0, 0);
+        let groups = group_processes(&[python, hidden], &GroupBy::Executable);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].key, "/usr/bin/python3.12");
+        assert_eq!(groups[1].key, "python3");
+    }
+}
@@ -0,0 +1,704 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use rusqlite::{Connection, OpenFlags, params};
+
+use crate::{
+    app::App,
+    app_args::{HistoryQuery, HistorySort, OutputFormat, QueryOptions},
+    rules::parse_duration,
+    system::Snapshot,
+};
+
+///Bumped whenever the tables change shape
+const SCHEMA_VERSION: i64 = 1;
+
+///Processes live in a table of their own, so a sample row is only the numbers that change
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS samples (
+    id INTEGER PRIMARY KEY,
+    time INTEGER NOT NULL,
+    resolution INTEGER NOT NULL,
+    cpu_percent REAL NOT NULL,
+    cpu_count INTEGER NOT NULL,
+    memory_total_kb INTEGER NOT NULL,
+    memory_available_kb INTEGER NOT NULL,
+    swap_total_kb INTEGER NOT NULL,
+    swap_free_kb INTEGER NOT NULL,
+    load1 REAL NOT NULL,
+    load5 REAL NOT NULL,
+    load15 REAL NOT NULL,
+    tasks INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS samples_time ON samples(time);
+CREATE TABLE IF NOT EXISTS processes (
+    id INTEGER PRIMARY KEY,
+    pid INTEGER NOT NULL,
+    start_time INTEGER NOT NULL,
+    ppid INTEGER NOT NULL,
+    user TEXT NOT NULL,
+    name TEXT NOT NULL,
+    command TEXT NOT NULL,
+    UNIQUE (pid, start_time)
+);
+CREATE TABLE IF NOT EXISTS process_samples (
+    sample_id INTEGER NOT NULL,
+    process_id INTEGER NOT NULL,
+    state TEXT NOT NULL,
+    cpu_percent REAL NOT NULL,
+    memory_kb INTEGER NOT NULL,
+    num_threads INTEGER NOT NULL,
+    nice INTEGER NOT NULL,
+    cpu_time_total INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS process_samples_sample ON process_samples(sample_id);
+CREATE INDEX IF NOT EXISTS process_samples_process ON process_samples(process_id);
+";
+
+///How long samples are kept, and how they thin out with age
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Retention {
+    ///Samples older than this are deleted
+    pub keep: Duration,
+    ///Samples older than this are merged into one per `step`
+    pub downsample_after: Duration,
+    pub step: Duration,
+}
+
+///Result of a query, printed as a table or CSV
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    pub columns: Vec<&'static str>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl QueryResult {
+    ///Columns padded to their widest value, the last one left as it is.
+    ///Line breaks, which commands can have, would break the rows up.
+    pub fn to_table(&self) -> String {
+        let rows: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|value| value.replace(char::is_control, " "))
+                    .collect()
+            })
+            .collect();
+        let mut widths: Vec<usize> = self.columns.iter().map(|c| c.chars().count()).collect();
+        for row in &rows {
+            for (width, value) in widths.iter_mut().zip(row) {
+                *width = (*width).max(value.chars().count());
+            }
+        }
+        let line = |values: Vec<&str>| {
+            let last = values.len().saturating_sub(1);
+            let cells: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| match i == last {
+                    true => value.to_string(),
+                    false => format!("{value:<width$}", width = widths[i]),
+                })
+                .collect();
+            cells.join("  ") + "\n"
+        };
+        let mut table = line(self.columns.clone());
+        for row in &rows {
+            table.push_str(&line(row.iter().map(String::as_str).collect()));
+        }
+        table
+    }
+
+    pub fn to_csv(&self) -> String {
+        let field = |value: &str| match value.contains([',', '"', '\n']) {
+            true => format!("\"{}\"", value.replace('"', "\"\"")),
+            false => value.to_string(),
+        };
+        let mut csv = self.columns.join(",") + "\n";
+        for row in &self.rows {
+            csv.push_str(&row.iter().map(|v| field(v)).collect::<Vec<_>>().join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+pub struct History {
+    connection: Connection,
+}
+
+impl History {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::setup(
+            Connection::open(path).with_context(|| format!("Cannot open {}", path.display()))?,
+        )
+    }
+
+    ///Open a history for queries only, which leave the file as it is
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Cannot open {}", path.display()))?;
+        if Self::schema_version(&connection)? == 0 {
+            bail!("{} is not a rustop history", path.display());
+        }
+        Ok(Self { connection })
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> Result<Self> {
+        Self::setup(Connection::open_in_memory()?)
+    }
+
+    ///Schema version of the file, 0 for one we never set up
+    fn schema_version(connection: &Connection) -> Result<i64> {
+        let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            bail!("The history was written by a newer rustop, schema version {version}");
+        }
+        Ok(version)
+    }
+
+    fn setup(connection: Connection) -> Result<Self> {
+        Self::schema_version(&connection)?;
+        //queries can read while the recorder writes
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.execute_batch(SCHEMA)?;
+        connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Self { connection })
+    }
+
+    ///Store a snapshot, taken `resolution` after the one before
+    pub fn record(&mut self, snapshot: &Snapshot, resolution: Duration) -> Result<()> {
+        let system = &snapshot.system;
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO samples (time, resolution, cpu_percent, cpu_count, memory_total_kb,
+                memory_available_kb, swap_total_kb, swap_free_kb, load1, load5, load15, tasks)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                snapshot.timestamp,
+                resolution.as_secs() as i64,
+                system.cpu_percent,
+                system.cpu_count as i64,
+                system.memory_total_kb as i64,
+                system.memory_available_kb as i64,
+                system.swap_total_kb as i64,
+                system.swap_free_kb as i64,
+                system.load_average[0],
+                system.load_average[1],
+                system.load_average[2],
+                system.tasks as i64,
+            ],
+        )?;
+        let sample_id = transaction.last_insert_rowid();
+        {
+            //a process that exec'd since the last sample has a new name
+            let mut upsert = transaction.prepare_cached(
+                "INSERT INTO processes (pid, start_time, ppid, user, name, command)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT (pid, start_time) DO UPDATE
+                    SET ppid = excluded.ppid, name = excluded.name, command = excluded.command
+                 RETURNING id",
+            )?;
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO process_samples (sample_id, process_id, state, cpu_percent,
+                    memory_kb, num_threads, nice, cpu_time_total)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            for process in &snapshot.processes {
+                let process_id: i64 = upsert.query_row(
+                    params![
+                        process.pid as i64,
+                        process.start_time as i64,
+                        process.ppid as i64,
+                        process.user,
+                        process.name,
+                        process.command,
+                    ],
+                    |row| row.get(0),
+                )?;
+                insert.execute(params![
+                    sample_id,
+                    process_id,
+                    process.state.as_char().to_string(),
+                    process.cpu_percent,
+                    process.memory_kb as i64,
+                    process.num_threads as i64,
+                    process.nice,
+                    process.cpu_time_total as i64,
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    ///Delete what is past retention and merge what is past full detail, as of `now`
+    pub fn maintain(&mut self, now: i64, retention: &Retention) -> Result<()> {
+        let step = (retention.step.as_secs() as i64).max(1);
+        let expired = now - retention.keep.as_secs() as i64;
+        //whole steps only, so a merged sample never gets a second helping
+        let cutoff = (now - retention.downsample_after.as_secs() as i64).div_euclid(step) * step;
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "DELETE FROM process_samples
+             WHERE sample_id IN (SELECT id FROM samples WHERE time < ?1)",
+            [expired],
+        )?;
+        transaction.execute("DELETE FROM samples WHERE time < ?1", [expired])?;
+
+        transaction.execute(
+            "INSERT INTO samples (time, resolution, cpu_percent, cpu_count, memory_total_kb,
+                memory_available_kb, swap_total_kb, swap_free_kb, load1, load5, load15, tasks)
+             SELECT (time / ?1) * ?1 AS bucket, ?1, AVG(cpu_percent), MAX(cpu_count),
+                MAX(memory_total_kb), CAST(AVG(memory_available_kb) AS INTEGER),
+                MAX(swap_total_kb), CAST(AVG(swap_free_kb) AS INTEGER),
+                AVG(load1), AVG(load5), AVG(load15), CAST(AVG(tasks) AS INTEGER)
+             FROM samples WHERE time < ?2 AND resolution < ?1
+             GROUP BY bucket",
+            [step, cutoff],
+        )?;
+        //cpu is spread over the whole step, so the cpu seconds add up the same as before.
+        //Memory and threads keep their peak, state and cpu time the last seen.
+        transaction.execute(
+            "INSERT INTO process_samples (sample_id, process_id, state, cpu_percent,
+                memory_kb, num_threads, nice, cpu_time_total)
+             SELECT merged.id, p.process_id,
+                substr(MAX(printf('%020d', s.time) || p.state), 21),
+                SUM(p.cpu_percent * s.resolution) / ?1,
+                MAX(p.memory_kb), MAX(p.num_threads), MAX(p.nice), MAX(p.cpu_time_total)
+             FROM process_samples p
+                JOIN samples s ON s.id = p.sample_id
+                JOIN samples merged ON merged.time = (s.time / ?1) * ?1 AND merged.resolution = ?1
+             WHERE s.time < ?2 AND s.resolution < ?1
+             GROUP BY merged.id, p.process_id",
+            [step, cutoff],
+        )?;
+        transaction.execute(
+            "DELETE FROM process_samples
+             WHERE sample_id IN (SELECT id FROM samples WHERE time < ?2 AND resolution < ?1)",
+            [step, cutoff],
+        )?;
+        transaction.execute(
+            "DELETE FROM samples WHERE time < ?2 AND resolution < ?1",
+            [step, cutoff],
+        )?;
+        transaction.execute(
+            "DELETE FROM processes
+             WHERE id NOT IN (SELECT DISTINCT process_id FROM process_samples)",
+            [],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    ///Processes by cpu seconds used, or peak memory, between `from` and `to`
+    pub fn top(&self, from: i64, to: i64, by: HistorySort, limit: usize) -> Result<QueryResult> {
+        let order = match by {
+            HistorySort::Cpu => "cpu_secs",
+            HistorySort::Memory => "max_rss",
+        };
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT pr.pid, pr.user, pr.name,
+                SUM(p.cpu_percent * s.resolution) / 100.0 AS cpu_secs,
+                SUM(p.cpu_percent * s.resolution) / SUM(s.resolution) AS avg_cpu,
+                MAX(p.cpu_percent), MAX(p.memory_kb) AS max_rss, pr.command
+             FROM process_samples p
+                JOIN samples s ON s.id = p.sample_id
+                JOIN processes pr ON pr.id = p.process_id
+             WHERE s.time >= ?1 AND s.time < ?2
+             GROUP BY pr.id
+             ORDER BY {order} DESC
+             LIMIT ?3"
+        ))?;
+        let rows = statement
+            .query_map(params![from, to, limit as i64], |row| {
+                Ok(vec![
+                    row.get::<_, i64>(0)?.to_string(),
+                    row.get(1)?,
+                    row.get(2)?,
+                    format!("{:.1}", row.get::<_, f64>(3)?),
+                    format!("{:.1}", row.get::<_, f64>(4)?),
+                    format!("{:.1}", row.get::<_, f64>(5)?),
+                    row.get::<_, i64>(6)?.to_string(),
+                    row.get(7)?,
+                ])
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(QueryResult {
+            columns: vec![
+                "PID",
+                "USER",
+                "NAME",
+                "CPU_SECS",
+                "AVG_CPU%",
+                "MAX_CPU%",
+                "MAX_RSS_KB",
+                "COMMAND",
+            ],
+            rows,
+        })
+    }
+
+    ///Every sample of `pid` between `from` and `to`, of whichever processes had it
+    pub fn process(&self, pid: u64, from: i64, to: i64) -> Result<QueryResult> {
+        let mut statement = self.connection.prepare(
+            "SELECT s.time, pr.name, p.state, p.cpu_percent, p.memory_kb, p.num_threads, p.nice
+             FROM process_samples p
+                JOIN samples s ON s.id = p.sample_id
+                JOIN processes pr ON pr.id = p.process_id
+             WHERE pr.pid = ?1 AND s.time >= ?2 AND s.time < ?3
+             ORDER BY s.time",
+        )?;
+        let rows = statement
+            .query_map(params![pid as i64, from, to], |row| {
+                Ok(vec![
+                    format_time(row.get(0)?),
+                    row.get(1)?,
+                    row.get(2)?,
+                    format!("{:.1}", row.get::<_, f64>(3)?),
+                    row.get::<_, i64>(4)?.to_string(),
+                    row.get::<_, i64>(5)?.to_string(),
+                    row.get::<_, i64>(6)?.to_string(),
+                ])
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(QueryResult {
+            columns: vec!["TIME", "NAME", "STATE", "CPU%", "RSS_KB", "THREADS", "NICE"],
+            rows,
+        })
+    }
+
+    ///The machine's numbers between `from` and `to`
+    pub fn system(&self, from: i64, to: i64) -> Result<QueryResult> {
+        let mut statement = self.connection.prepare(
+            "SELECT time, cpu_percent, memory_total_kb, memory_available_kb,
+                swap_total_kb - swap_free_kb, load1, load5, load15, tasks
+             FROM samples WHERE time >= ?1 AND time < ?2
+             ORDER BY time",
+        )?;
+        let rows = statement
+            .query_map(params![from, to], |row| {
+                let total: i64 = row.get(2)?;
+                let available: i64 = row.get(3)?;
+                Ok(vec![
+                    format_time(row.get(0)?),
+                    format!("{:.1}", row.get::<_, f64>(1)?),
+                    (total - available).to_string(),
+                    row.get::<_, i64>(4)?.to_string(),
+                    format!("{:.2}", row.get::<_, f64>(5)?),
+                    format!("{:.2}", row.get::<_, f64>(6)?),
+                    format!("{:.2}", row.get::<_, f64>(7)?),
+                    row.get::<_, i64>(8)?.to_string(),
+                ])
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(QueryResult {
+            columns: vec![
+                "TIME",
+                "CPU%",
+                "MEM_USED_KB",
+                "SWAP_USED_KB",
+                "LOAD1",
+                "LOAD5",
+                "LOAD15",
+                "TASKS",
+            ],
+            rows,
+        })
+    }
+}
+
+fn format_time(epoch_secs: i64) -> String {
+    Local
+        .timestamp_opt(epoch_secs, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| epoch_secs.to_string())
+}
+
+///Where the history is kept when --db is not given
+pub fn default_db_path() -> Result<PathBuf> {
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .ok_or_else(|| anyhow!("Neither XDG_DATA_HOME nor HOME is set, pass --db"))?;
+    Ok(data.join("rustop").join("history.db"))
+}
+
+///Seconds since the epoch of "now", a time of day like "02:00" at its last occurrence,
+///a date like "2025-07-01" or "2025-07-01 02:00", or how long ago like "90m"
+pub fn parse_time(value: &str, now: DateTime<Local>) -> Result<i64> {
+    let value = value.trim();
+    if value == "now" {
+        return Ok(now.timestamp());
+    }
+    let local = |naive: NaiveDateTime| {
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|time| time.timestamp())
+            .ok_or_else(|| anyhow!("'{value}' does not exist in the local time zone"))
+    };
+    for format in ["%H:%M", "%H:%M:%S"] {
+        if let Ok(time) = NaiveTime::parse_from_str(value, format) {
+            let today = local(now.date_naive().and_time(time))?;
+            return Ok(match today > now.timestamp() {
+                true => today - 86400,
+                false => today,
+            });
+        }
+    }
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(value, format) {
+            return local(time);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return local(date.and_time(NaiveTime::MIN));
+    }
+    match parse_duration(value) {
+        Ok(ago) => Ok(now.timestamp() - ago.as_secs() as i64),
+        Err(_) => bail!("'{value}' is not a time, a date or how long ago"),
+    }
+}
+
+///Start and end of a query. An end before the start is the next day's,
+///so "--from 23:00 --to 01:00" spans midnight.
+pub fn query_window(options: &QueryOptions, now: DateTime<Local>) -> Result<(i64, i64)> {
+    let from = parse_time(&options.from, now)?;
+    let mut to = match &options.to {
+        Some(to) => parse_time(to, now)?,
+        None => now.timestamp(),
+    };
+    if to <= from && options.to.is_some() {
+        to += 86400;
+    }
+    if to <= from {
+        bail!("Nothing between --from and --to");
+    }
+    Ok((from, to))
+}
+
+///Sample every `interval` until killed, pruning and merging once a step
+pub fn run_record(db: Option<PathBuf>, interval: Duration, retention: Retention) -> Result<()> {
+    if interval.is_zero() {
+        bail!("--interval has to be at least 1s");
+    }
+    let path = match db {
+        Some(path) => path,
+        None => default_db_path()?,
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Cannot create {}", parent.display()))?;
+    }
+    let mut history = History::open(&path)?;
+    println!(
+        "Recording to {} every {}s, keeping {}s",
+        path.display(),
+        interval.as_secs(),
+        retention.keep.as_secs()
+    );
+    let mut app = App::new();
+    //cpu percentages need a refresh to compare against
+    app.update_processes()?;
+    let mut maintained_at = 0;
+    loop {
+        std::thread::sleep(interval);
+        app.update_processes()?;
+        let snapshot = Snapshot::take(&app.processes, app.pressure.current.clone())?;
+        history.record(&snapshot, interval)?;
+        if snapshot.timestamp - maintained_at >= retention.step.as_secs() as i64 {
+            history.maintain(snapshot.timestamp, &retention)?;
+            maintained_at = snapshot.timestamp;
+        }
+    }
+}
+
+pub fn run_history(db: Option<PathBuf>, query: &HistoryQuery) -> Result<()> {
+    let path = match db {
+        Some(path) => path,
+        None => default_db_path()?,
+    };
+    if !path.exists() {
+        bail!(
+            "No history at {}, start `rustop record` first",
+            path.display()
+        );
+    }
+    let history = History::open_read_only(&path)?;
+    let now = Local::now();
+    let (result, options) = match query {
+        HistoryQuery::Top { by, limit, options } => {
+            let (from, to) = query_window(options, now)?;
+            (history.top(from, to, *by, *limit)?, options)
+        }
+        HistoryQuery::Process { pid, options } => {
+            let (from, to) = query_window(options, now)?;
+            (history.process(*pid, from, to)?, options)
+        }
+        HistoryQuery::System { options } => {
+            let (from, to) = query_window(options, now)?;
+            (history.system(from, to)?, options)
+        }
+    };
+    match options.format {
+        OutputFormat::Table if result.rows.is_empty() => eprintln!("No samples in that window"),
+        OutputFormat::Table => print!("{}", result.to_table()),
+        OutputFormat::Csv => print!("{}", result.to_csv()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        model::{HostClock, ProcessInfo},
+        system::SystemStats,
+    };
+
+    fn snapshot(timestamp: i64, busy_cpu: f64) -> Snapshot {
+        let mut busy = ProcessInfo::new();
+        busy.pid = 10;
+        busy.start_time = 100;
+        busy.name = "make".to_string();
+        busy.command = "make -j8, all".to_string();
+        busy.cpu_percent = busy_cpu;
+        busy.memory_kb = 1000 + timestamp as u64;
+        let mut idle = ProcessInfo::new();
+        idle.pid = 1;
+        idle.name = "init".to_string();
+        idle.memory_kb = 50_000;
+        Snapshot {
+            timestamp,
+            clock: HostClock {
+                boot_time: 0,
+                clock_ticks: 100.0,
+            },
+            system: SystemStats {
+                cpu_percent: busy_cpu,
+                memory_total_kb: 1000,
+                ..SystemStats::default()
+            },
+            processes: vec![busy, idle],
+        }
+    }
+
+    #[test]
+    pub fn test_record_and_query() -> Result<()> {
+        let mut history = History::in_memory()?;
+        let minute = Duration::from_secs(60);
+        for (i, cpu) in [50.0, 100.0, 0.0].into_iter().enumerate() {
+            history.record(&snapshot(6000 + 60 * i as i64, cpu), minute)?;
+        }
+        let top = history.top(0, 10_000, HistorySort::Cpu, 10)?;
+        assert_eq!(top.rows[0][..4], ["10", "", "make", "90.0"]);
+        assert_eq!(top.rows[0][5], "100.0");
+        let by_memory = history.top(0, 10_000, HistorySort::Memory, 1)?;
+        assert_eq!(by_memory.rows.len(), 1);
+        assert_eq!(by_memory.rows[0][0], "1");
+        //the window leaves out the first sample
+        assert_eq!(history.process(10, 6060, 10_000)?.rows.len(), 2);
+        assert_eq!(history.system(0, 10_000)?.rows.len(), 3);
+        assert!(top.to_csv().contains(",\"make -j8, all\"\n"));
+        assert!(top.to_table().starts_with("PID  USER  NAME  CPU_SECS"));
+        let multiline = QueryResult {
+            columns: vec!["PID", "COMMAND"],
+            rows: vec![vec!["1".to_string(), "sh -c 'a\nb'".to_string()]],
+        };
+        assert_eq!(multiline.to_table(), "PID  COMMAND\n1    sh -c 'a b'\n");
+
+        //all three fall in one 10 minute step, which is past full detail
+        let retention = Retention {
+            keep: Duration::from_secs(86400),
+            downsample_after: Duration::from_secs(60),
+            step: Duration::from_secs(600),
+        };
+        history.maintain(7000, &retention)?;
+        let samples = history.system(0, 10_000)?;
+        assert_eq!(samples.rows.len(), 1);
+        assert_eq!(samples.rows[0][1], "50.0");
+        //merging keeps the cpu seconds and the peak memory
+        let merged = history.top(0, 10_000, HistorySort::Cpu, 10)?;
+        assert_eq!(merged.rows[0][3], "90.0");
+        assert_eq!(merged.rows[0][6], "7120");
+        history.maintain(7000, &retention)?;
+        assert_eq!(history.system(0, 10_000)?.rows.len(), 1);
+
+        history.maintain(100_000, &retention)?;
+        assert!(history.system(0, 200_000)?.rows.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_open_read_only() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("history.db");
+        History::open(&path)?.record(&snapshot(6000, 50.0), Duration::from_secs(60))?;
+        let history = History::open_read_only(&path)?;
+        assert_eq!(history.system(0, 10_000)?.rows.len(), 1);
+        assert!(
+            history
+                .connection
+                .execute("DELETE FROM samples", [])
+                .is_err()
+        );
+
+        let empty = dir.path().join("empty.db");
+        Connection::open(&empty)?.execute_batch("CREATE TABLE other (id INTEGER)")?;
+        assert!(History::open_read_only(&empty).is_err());
+        //and it was left as it was, without the history tables
+        let tables: i64 = Connection::open(&empty)?.query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'table'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(tables, 1);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_parse_time() -> Result<()> {
+        let now = Local.with_ymd_and_hms(2025, 7, 1, 2, 30, 0).unwrap();
+        let at = |y, mo, d, h, mi| {
+            Local
+                .with_ymd_and_hms(y, mo, d, h, mi, 0)
+                .unwrap()
+                .timestamp()
+        };
+        assert_eq!(parse_time("02:00", now)?, at(2025, 7, 1, 2, 0));
+        //not yet today, so yesterday's
+        assert_eq!(parse_time("03:00", now)?, at(2025, 6, 30, 3, 0));
+        assert_eq!(parse_time("2025-06-01 12:15", now)?, at(2025, 6, 1, 12, 15));
+        assert_eq!(parse_time("2025-06-01", now)?, at(2025, 6, 1, 0, 0));
+        assert_eq!(parse_time("1d", now)?, at(2025, 6, 30, 2, 30));
+        assert!(parse_time("yesterday", now).is_err());
+
+        let window = |from: &str, to: Option<&str>| {
+            query_window(
+                &QueryOptions {
+                    from: from.to_string(),
+                    to: to.map(String::from),
+                    format: OutputFormat::Table,
+                },
+                now,
+            )
+        };
+        assert_eq!(
+            window("02:00", Some("03:00"))?,
+            (at(2025, 7, 1, 2, 0), at(2025, 7, 1, 3, 0))
+        );
+        assert_eq!(
+            window("1h", None)?,
+            (at(2025, 7, 1, 1, 30), now.timestamp())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::model::ProcessState;
+
+///Where in the kernel one thread of a process is waiting
+#[derive(Debug, Clone, Serialize)]
+pub struct ThreadStack {
+    pub tid: u64,
+    pub name: String,
+    pub state: ProcessState,
+    pub wchan: String,
+    ///Kernel stack, innermost frame first, or why we couldn't read it (usually needs root)
+    pub stack: Result<Vec<String>, String>,
+}
+
+///Kernel function the task is blocked in, empty when running or not allowed to know
+pub fn read_wchan(task_dir: &Path) -> String {
+    match std::fs::read_to_string(task_dir.join("wchan")) {
+        //"0" means the task isn't blocked
+        Ok(wchan) if wchan.trim() != "0" => wchan.trim().to_string(),
+        _ => String::new(),
+    }
+}
+
+///Turn "[<0>] do_sys_poll+0x3d1/0x570" lines into "do_sys_poll+0x3d1/0x570"
+pub fn parse_stack(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| match line.split_once("] ") {
+            Some((_, frame)) => frame.trim().to_string(),
+            None => line.trim().to_string(),
+        })
+        .filter(|frame| !frame.is_empty())
+        .collect()
+}
+
+///Wait channel and kernel stack of every thread of a process
+pub fn read_thread_stacks(pid: u64) -> Vec<ThreadStack> {
+    let Ok(entries) = std::fs::read_dir(format!("/proc/{pid}/task")) else {
+        return Vec::new();
+    };
+    let mut threads: Vec<ThreadStack> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|entry| {
+            let tid = entry.file_name().to_str()?.parse::<u64>().ok()?;
+            let task_dir = entry.path();
+            let status = std::fs::read_to_string(task_dir.join("status")).unwrap_or_default();
+            let field = |name: &str| {
+                status
+                    .lines()
+                    .find_map(|l| l.strip_prefix(name))
+                    .map(|v| v.trim().to_string())
+                    .unwrap_or_default()
+            };
+            let state = ProcessState::from(field("State:").chars().next().unwrap_or('?'));
+            Some(ThreadStack {
+                tid,
+                name: field("Name:"),
+                state,
+                wchan: read_wchan(&task_dir),
+                stack: std::fs::read_to_string(task_dir.join("stack"))
+                    .map(|s| parse_stack(&s))
+                    .map_err(|e| e.to_string()),
+            })
+        })
+        .collect();
+    threads.sort_by_key(|t| t.tid);
+    threads
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_stack() {
+        let stack = parse_stack(
+            "[<0>] rpc_wait_bit_killable+0x1e/0xa0 [sunrpc]\n\
+             [<0>] __rpc_execute+0x10f/0x3e0 [sunrpc]\n\
+             [<0>] nfs_file_read+0x6b/0xa0 [nfs]\n",
+        );
+        assert_eq!(stack.len(), 3);
+        assert_eq!(stack[0], "rpc_wait_bit_killable+0x1e/0xa0 [sunrpc]");
+        assert!(parse_stack("").is_empty());
+    }
+}
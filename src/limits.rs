@@ -0,0 +1,200 @@
+use anyhow::{Result, anyhow, bail};
+use serde::Serialize;
+
+///One line of `/proc/<pid>/limits`, `None` meaning unlimited
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Limit {
+    ///e.g. "Max open files"
+    pub name: String,
+    pub soft: Option<u64>,
+    pub hard: Option<u64>,
+    ///"files", "bytes", "seconds"... empty for nice and realtime priority
+    pub units: String,
+}
+
+fn parse_limit_value(value: &str) -> Result<Option<u64>> {
+    match value {
+        "unlimited" => Ok(None),
+        value => Ok(Some(value.parse::<u64>()?)),
+    }
+}
+
+pub fn format_limit_value(value: Option<u64>) -> String {
+    value
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "unlimited".to_string())
+}
+
+///Parse `/proc/<pid>/limits`. Names have single spaces in them ("Max open files"),
+///the columns are separated by runs of them.
+pub fn parse_limits(content: &str) -> Result<Vec<Limit>> {
+    content
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut columns = line.split("  ").map(|c| c.trim()).filter(|c| !c.is_empty());
+            let mut next = |what: &str| {
+                columns
+                    .next()
+                    .ok_or_else(|| anyhow!("No {what} in limits line '{line}'"))
+            };
+            Ok(Limit {
+                name: next("name")?.to_string(),
+                soft: parse_limit_value(next("soft limit")?)?,
+                hard: parse_limit_value(next("hard limit")?)?,
+                units: columns.next().unwrap_or("").to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn read_limits(pid: u64) -> Result<Vec<Limit>> {
+    parse_limits(&std::fs::read_to_string(format!("/proc/{pid}/limits"))?)
+}
+
+///Parse what the user typed to change a limit: "<soft> <hard>", or one value for both
+pub fn parse_limit_input(input: &str) -> Result<(Option<u64>, Option<u64>)> {
+    let values = input
+        .split_whitespace()
+        .map(parse_limit_value)
+        .collect::<Result<Vec<_>>>()
+        .map_err(|_| anyhow!("Limits are numbers or 'unlimited'"))?;
+    match values.as_slice() {
+        [both] => Ok((*both, *both)),
+        [soft, hard] => Ok((*soft, *hard)),
+        _ => bail!("Expected '<soft> <hard>'"),
+    }
+}
+
+///What a process currently uses of the limits we can measure
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LimitUsage {
+    pub open_files: Option<u64>,
+    pub threads: Option<u64>,
+    pub address_space_bytes: Option<u64>,
+}
+
+impl LimitUsage {
+    pub fn read(pid: u64) -> Self {
+        let open_files = std::fs::read_dir(format!("/proc/{pid}/fd"))
+            .ok()
+            .map(|entries| entries.count() as u64);
+        let status = std::fs::read_to_string(format!("/proc/{pid}/status")).unwrap_or_default();
+        let field = |name: &str| {
+            status
+                .lines()
+                .find_map(|l| l.strip_prefix(name))
+                .and_then(|v| v.split_whitespace().next())
+                .and_then(|v| v.parse::<u64>().ok())
+        };
+        Self {
+            open_files,
+            threads: field("Threads:"),
+            address_space_bytes: field("VmSize:").map(|kb| kb * 1024),
+        }
+    }
+
+    ///Current usage counted against the named limit, for the ones we know how to measure
+    pub fn for_limit(&self, name: &str) -> Option<u64> {
+        match name {
+            "Max open files" => self.open_files,
+            //RLIMIT_NPROC counts every thread of the user, this is only the process's share
+            "Max processes" => self.threads,
+            "Max address space" => self.address_space_bytes,
+            _ => None,
+        }
+    }
+}
+
+///Change a limit of another process with prlimit(2), needs CAP_SYS_RESOURCE
+///to raise the hard limit or touch other users' processes
+pub fn set_limit(pid: u64, name: &str, soft: Option<u64>, hard: Option<u64>) -> Result<()> {
+    let resource = match name {
+        "Max cpu time" => libc::RLIMIT_CPU,
+        "Max file size" => libc::RLIMIT_FSIZE,
+        "Max data size" => libc::RLIMIT_DATA,
+        "Max stack size" => libc::RLIMIT_STACK,
+        "Max core file size" => libc::RLIMIT_CORE,
+        "Max resident set" => libc::RLIMIT_RSS,
+        "Max processes" => libc::RLIMIT_NPROC,
+        "Max open files" => libc::RLIMIT_NOFILE,
+        "Max locked memory" => libc::RLIMIT_MEMLOCK,
+        "Max address space" => libc::RLIMIT_AS,
+        "Max file locks" => libc::RLIMIT_LOCKS,
+        "Max pending signals" => libc::RLIMIT_SIGPENDING,
+        "Max msgqueue size" => libc::RLIMIT_MSGQUEUE,
+        "Max nice priority" => libc::RLIMIT_NICE,
+        "Max realtime priority" => libc::RLIMIT_RTPRIO,
+        "Max realtime timeout" => libc::RLIMIT_RTTIME,
+        _ => bail!("Unknown limit '{name}'"),
+    };
+    let new_limit = libc::rlimit64 {
+        rlim_cur: soft.unwrap_or(libc::RLIM64_INFINITY),
+        rlim_max: hard.unwrap_or(libc::RLIM64_INFINITY),
+    };
+    //SAFETY: new_limit outlives the call and a null old limit is allowed
+    let result = unsafe {
+        libc::prlimit64(
+            pid as libc::pid_t,
+            resource,
+            &new_limit,
+            std::ptr::null_mut(),
+        )
+    };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LIMITS: &str = "\
+Limit                     Soft Limit           Hard Limit           Units
+Max cpu time              unlimited            unlimited            seconds
+Max processes             127431               127431               processes
+Max open files            1024                 524288               files
+Max address space         unlimited            unlimited            bytes
+Max nice priority         0                    0
+";
+
+    #[test]
+    pub fn test_parse_limits() -> Result<()> {
+        let limits = parse_limits(LIMITS)?;
+        assert_eq!(limits.len(), 5);
+        assert_eq!(limits[0].name, "Max cpu time");
+        assert_eq!(limits[0].soft, None);
+        assert_eq!(limits[2].soft, Some(1024));
+        assert_eq!(limits[2].hard, Some(524288));
+        assert_eq!(limits[2].units, "files");
+        assert_eq!(limits[4].units, "");
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_parse_limit_input() -> Result<()> {
+        assert_eq!(parse_limit_input("4096 8192")?, (Some(4096), Some(8192)));
+        assert_eq!(parse_limit_input("unlimited")?, (None, None));
+        assert!(parse_limit_input("lots").is_err());
+        assert!(parse_limit_input("1 2 3").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_set_own_limit() -> Result<()> {
+        //setting our own limit to what it already is needs no privileges
+        let pid = std::process::id() as u64;
+        let limits = read_limits(pid)?;
+        let files = limits
+            .iter()
+            .find(|l| l.name == "Max open files")
+            .ok_or_else(|| anyhow!("No open files limit"))?;
+        set_limit(pid, &files.name, files.soft, files.hard)?;
+        assert!(set_limit(pid, "Max fun", None, None).is_err());
+        assert!(LimitUsage::read(pid).open_files.is_some());
+        Ok(())
+    }
+}
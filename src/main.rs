@@ -1,18 +1,49 @@
 use std::io::stdout;
 
 use anyhow::{Context, Result};
+use app_args::{AppArgs, Mode};
+use clap::Parser;
 use crossterm::{
     ExecutableCommand,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
+use history::Retention;
+use protocol::Endpoint;
 use ratatui::{Terminal, prelude::CrosstermBackend};
 use tui::run_tui;
 
+mod affinity;
+mod agent;
 mod app;
 mod app_args;
+mod collector;
+mod commands;
+mod detail;
+mod disk;
+mod events;
+mod groups;
+mod history;
+mod kstack;
+mod limits;
+mod memmap;
 mod model;
+mod net;
+mod nice;
+mod oom;
 mod output;
+mod overview;
+mod pressure;
+mod proc_connector;
 mod processes;
+mod prompt;
+mod protocol;
+mod remote;
+mod rules;
+mod security;
+mod sensors;
+mod server;
+mod signals;
+mod system;
 mod tui;
 
 //TODO sort by name
@@ -21,11 +52,34 @@ fn main() -> Result<()> {
 }
 
 fn run() -> Result<()> {
+    let args = AppArgs::parse();
+    match &args.mode {
+        Some(Mode::Serve { listen, interval }) => return server::serve(listen, *interval),
+        Some(Mode::Agent { listen, interval }) => {
+            return agent::run_agent(&Endpoint::parse(listen), *interval, args.token.clone());
+        }
+        Some(Mode::Record {
+            db,
+            interval,
+            retention,
+            downsample_after,
+            downsample_to,
+        }) => {
+            let retention = Retention {
+                keep: *retention,
+                downsample_after: *downsample_after,
+                step: *downsample_to,
+            };
+            return history::run_record(db.clone(), *interval, retention);
+        }
+        Some(Mode::History { db, query }) => return history::run_history(db.clone(), query),
+        None => {}
+    }
     enable_raw_mode().context("Failed to enable raw mode")?;
     std::io::stdout().execute(EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout());
     let mut terminal = Terminal::new(backend)?;
-    let result = run_tui(&mut terminal);
+    let result = run_tui(&mut terminal, &args);
     std::io::stdout().execute(LeaveAlternateScreen)?;
     disable_raw_mode().context("Failed to disable raw mode")?;
     result
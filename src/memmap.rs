@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+
+///One mapping from `/proc/<pid>/maps`, with usage when `smaps` was readable
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryRegion {
+    pub start: u64,
+    pub end: u64,
+    ///e.g. "r-xp"
+    pub perms: String,
+    pub offset: u64,
+    ///Backing file or pseudo path such as "[heap]", empty for anonymous memory
+    pub pathname: String,
+    pub rss_kb: Option<u64>,
+    pub pss_kb: Option<u64>,
+}
+
+impl MemoryRegion {
+    pub fn size_kb(&self) -> u64 {
+        self.end.saturating_sub(self.start) / 1024
+    }
+
+    ///What the region is grouped under in the summary
+    pub fn mapping_name(&self) -> &str {
+        if self.pathname.is_empty() {
+            "[anon]"
+        } else {
+            //deleted files still show up, keep them with their original name
+            self.pathname.trim_end_matches(" (deleted)")
+        }
+    }
+}
+
+///All the regions belonging to one mapping: heap, stack, a shared library, anonymous memory...
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MappingSummary {
+    pub name: String,
+    pub regions: usize,
+    pub size_kb: u64,
+    pub rss_kb: u64,
+    pub pss_kb: u64,
+}
+
+///Parse the header line of a region, "start-end perms offset dev inode [pathname]"
+fn parse_region_line(line: &str) -> Result<MemoryRegion> {
+    let mut fields = line.splitn(6, ' ');
+    let mut next = |what: &str| {
+        fields
+            .next()
+            .ok_or_else(|| anyhow!("No {what} in map line '{line}'"))
+    };
+    let (start, end) = next("range")?
+        .split_once('-')
+        .ok_or_else(|| anyhow!("Bad address range in '{line}'"))?;
+    let perms = next("perms")?.to_string();
+    let offset = u64::from_str_radix(next("offset")?, 16)?;
+    let _dev = next("dev")?;
+    let _inode = next("inode")?;
+    let pathname = fields.next().unwrap_or("").trim().to_string();
+    Ok(MemoryRegion {
+        start: u64::from_str_radix(start, 16)?,
+        end: u64::from_str_radix(end, 16)?,
+        perms,
+        offset,
+        pathname,
+        rss_kb: None,
+        pss_kb: None,
+    })
+}
+
+///Region header lines start with a hex address range, attribute lines with "Name:"
+fn is_region_line(line: &str) -> bool {
+    line.split_whitespace()
+        .next()
+        .and_then(|range| range.split_once('-'))
+        .is_some_and(|(start, _)| u64::from_str_radix(start, 16).is_ok())
+}
+
+///Parse `/proc/<pid>/maps` or `/proc/<pid>/smaps`, the latter filling in Rss and Pss
+pub fn parse_maps(content: &str) -> Result<Vec<MemoryRegion>> {
+    let mut regions: Vec<MemoryRegion> = Vec::new();
+    for line in content.lines() {
+        if is_region_line(line) {
+            regions.push(parse_region_line(line)?);
+            continue;
+        }
+        let Some(region) = regions.last_mut() else {
+            continue;
+        };
+        let mut fields = line.split_whitespace();
+        let value = |v: Option<&str>| v.and_then(|v| v.parse::<u64>().ok());
+        match fields.next() {
+            Some("Rss:") => region.rss_kb = value(fields.next()),
+            Some("Pss:") => region.pss_kb = value(fields.next()),
+            _ => {}
+        }
+    }
+    Ok(regions)
+}
+
+///Regions of a process, from smaps when we may read it and maps otherwise
+pub fn read_memory_map(pid: u64) -> Result<Vec<MemoryRegion>> {
+    match std::fs::read_to_string(format!("/proc/{pid}/smaps")) {
+        Ok(smaps) => parse_maps(&smaps),
+        Err(_) => parse_maps(&std::fs::read_to_string(format!("/proc/{pid}/maps"))?),
+    }
+}
+
+///Total the regions per mapping, largest resident first
+pub fn summarize(regions: &[MemoryRegion]) -> Vec<MappingSummary> {
+    let mut by_name: HashMap<&str, MappingSummary> = HashMap::new();
+    for region in regions {
+        let summary = by_name
+            .entry(region.mapping_name())
+            .or_insert_with(|| MappingSummary {
+                name: region.mapping_name().to_string(),
+                ..MappingSummary::default()
+            });
+        summary.regions += 1;
+        summary.size_kb += region.size_kb();
+        summary.rss_kb += region.rss_kb.unwrap_or(0);
+        summary.pss_kb += region.pss_kb.unwrap_or(0);
+    }
+    let mut summaries: Vec<MappingSummary> = by_name.into_values().collect();
+    summaries.sort_by(|a, b| {
+        b.rss_kb
+            .cmp(&a.rss_kb)
+            .then(b.size_kb.cmp(&a.size_kb))
+            .then(a.name.cmp(&b.name))
+    });
+    summaries
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SMAPS: &str = "\
+55d4c8a00000-55d4c8a21000 rw-p 00000000 00:00 0                          [heap]
+Size:                132 kB
+Rss:                  12 kB
+Pss:                  12 kB
+7f1c2a000000-7f1c2a028000 r--p 00000000 fd:01 1835023                    /usr/lib/x86_64-linux-gnu/libc.so.6
+Size:                160 kB
+Rss:                 160 kB
+Pss:                  20 kB
+7f1c2a028000-7f1c2a1bd000 r-xp 00028000 fd:01 1835023                    /usr/lib/x86_64-linux-gnu/libc.so.6
+Size:               1620 kB
+Rss:                 900 kB
+Pss:                 100 kB
+7f1c2a200000-7f1c2a300000 rw-p 00000000 00:00 0
+Size:               1024 kB
+Rss:                   4 kB
+Pss:                   4 kB
+";
+
+    #[test]
+    pub fn test_parse_smaps() -> Result<()> {
+        let regions = parse_maps(SMAPS)?;
+        assert_eq!(regions.len(), 4);
+        assert_eq!(regions[0].pathname, "[heap]");
+        assert_eq!(regions[0].size_kb(), 132);
+        assert_eq!(regions[1].perms, "r--p");
+        assert_eq!(regions[2].offset, 0x28000);
+        assert_eq!(regions[2].rss_kb, Some(900));
+        assert_eq!(regions[3].mapping_name(), "[anon]");
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_parse_maps_without_usage() -> Result<()> {
+        let maps: String = SMAPS
+            .lines()
+            .filter(|l| is_region_line(l))
+            .map(|l| format!("{l}\n"))
+            .collect();
+        let regions = parse_maps(&maps)?;
+        assert_eq!(regions.len(), 4);
+        assert_eq!(regions[1].rss_kb, None);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_summarize() -> Result<()> {
+        let summary = summarize(&parse_maps(SMAPS)?);
+        assert_eq!(summary.len(), 3);
+        assert_eq!(summary[0].name, "/usr/lib/x86_64-linux-gnu/libc.so.6");
+        assert_eq!(summary[0].regions, 2);
+        assert_eq!(summary[0].rss_kb, 1060);
+        assert_eq!(summary[0].pss_kb, 120);
+        Ok(())
+    }
+}
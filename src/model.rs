@@ -1,7 +1,36 @@
-use std::time::Instant;
+use std::{collections::VecDeque, time::Instant};
 
-///Possible states of a process
-#[derive(Debug, Clone, PartialEq)]
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    processes::{get_boot_time, get_clock_ticks},
+    security::SecurityContext,
+};
+
+///How many samples of history we keep for sparklines
+pub const HISTORY_LEN: usize = 120;
+
+///What turns the clock ticks in `/proc/<pid>/stat` into seconds and dates on a host
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HostClock {
+    ///Seconds since the epoch
+    pub boot_time: u64,
+    pub clock_ticks: f64,
+}
+
+impl HostClock {
+    pub fn local() -> Self {
+        Self {
+            boot_time: get_boot_time(),
+            clock_ticks: get_clock_ticks(),
+        }
+    }
+}
+
+///Possible states of a process, serialized as the letter `/proc/<pid>/stat` uses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "char", from = "char")]
 pub enum ProcessState {
     Running,       // R - Running or runnable (on run queue)
     Sleeping,      // S - Interruptible sleep (waiting for an event to complete)
@@ -34,6 +63,12 @@ impl From<char> for ProcessState {
     }
 }
 
+impl From<ProcessState> for char {
+    fn from(state: ProcessState) -> Self {
+        state.as_char()
+    }
+}
+
 impl ProcessState {
     pub fn as_char(&self) -> char {
         match self {
@@ -70,23 +105,45 @@ impl ProcessState {
 }
 ///
 ///Struct to hold information about processes
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct ProcessInfo {
     pub pid: u64,
     pub ppid: u64,
     pub name: String,
     pub command: String,
+    ///Resolved `/proc/<pid>/exe`, empty when we are not allowed to read it
+    pub exe: String,
     pub cpu_percent: f64,
     ///how much cpu time has been used
     pub cpu_time_total: u64,
     ///previous cpu time measurement
     pub last_cpu_time: Option<u64>,
     ///When, if we did, did we last measure the cpu time
+    #[serde(skip)]
     pub last_measurement: Option<Instant>,
     pub memory_kb: u64,
     pub start_time: u64,
     pub state: ProcessState,
+    ///Kernel function a sleeping process is blocked in, empty otherwise
+    pub wchan: String,
+    ///Page faults and context switches since the process started
+    pub activity: ActivityCounters,
+    ///previous activity measurement, taken with `last_measurement`
+    #[serde(skip)]
+    pub last_activity: Option<ActivityCounters>,
+    ///Per second rates of the activity counters
+    pub activity_rates: ActivityRates,
+    ///How likely the OOM killer picks this process, 0 to 2000
+    pub oom_score: i64,
+    ///Adjustment added to `oom_score`, -1000 to 1000
+    pub oom_score_adj: i64,
+    ///Real and effective credentials, capabilities, seccomp and LSM label
+    pub security: SecurityContext,
+    ///CPU the process last ran on
+    pub last_cpu: Option<u32>,
+    ///CPUs the process may run on, as the kernel lists them e.g. "0-3,8"
+    pub cpus_allowed: String,
     pub user: String,
 
     // Additional useful fields for a process monitor
@@ -96,6 +153,12 @@ pub struct ProcessInfo {
     pub virtual_memory_kb: u64, // Virtual memory size
     pub session_id: u64,        // Session ID
     pub terminal: String,       // Controlling terminal (e.g., "pts/0", "tty1")
+    ///Name of the host the process runs on, None for this one
+    #[serde(skip)]
+    pub host: Option<String>,
+    ///Clock of the host the process runs on, None for this one
+    #[serde(skip)]
+    pub host_clock: Option<HostClock>,
 }
 
 #[allow(dead_code)]
@@ -106,6 +169,7 @@ impl ProcessInfo {
             ppid: 0,
             name: String::new(),
             command: String::new(),
+            exe: String::new(),
             cpu_percent: 0.0,
             cpu_time_total: 0,
             last_cpu_time: None,
@@ -113,6 +177,15 @@ impl ProcessInfo {
             memory_kb: 0,
             start_time: 0,
             state: ProcessState::Unknown('?'),
+            wchan: String::new(),
+            activity: ActivityCounters::default(),
+            last_activity: None,
+            activity_rates: ActivityRates::default(),
+            oom_score: 0,
+            oom_score_adj: 0,
+            security: SecurityContext::default(),
+            last_cpu: None,
+            cpus_allowed: String::new(),
             user: String::new(),
             priority: 0,
             nice: 0,
@@ -120,6 +193,8 @@ impl ProcessInfo {
             virtual_memory_kb: 0,
             session_id: 0,
             terminal: String::new(),
+            host: None,
+            host_clock: None,
         }
     }
 
@@ -133,11 +208,33 @@ impl ProcessInfo {
         format_bytes(self.virtual_memory_kb * 1024)
     }
 
+    pub fn clock(&self) -> HostClock {
+        self.host_clock.unwrap_or_else(HostClock::local)
+    }
+
+    /// Get when the process started, in seconds since the epoch
+    pub fn start_epoch_secs(&self) -> f64 {
+        let clock = self.clock();
+        clock.boot_time as f64 + self.start_time as f64 / clock.clock_ticks
+    }
+
+    /// Get when the process started as local wall-clock time
+    pub fn started_at(&self) -> DateTime<Local> {
+        Local
+            .timestamp_opt(self.start_epoch_secs() as i64, 0)
+            .single()
+            .unwrap_or_else(Local::now)
+    }
+
     /// Get process age since start_time (in seconds)
     pub fn age_seconds(&self) -> u64 {
-        // You'll need to implement this based on system uptime
-        // and convert start_time from clock ticks
-        0 // Placeholder
+        let now = Local::now().timestamp() as f64;
+        (now - self.start_epoch_secs()).max(0.0) as u64
+    }
+
+    /// Get the cpu time used so far (user and system), in seconds
+    pub fn cpu_time_secs(&self) -> f64 {
+        self.cpu_time_total as f64 / self.clock().clock_ticks
     }
 
     /// Check if process is a kernel thread (usually indicated by brackets)
@@ -148,14 +245,19 @@ impl ProcessInfo {
 
     /// Get short command name (without path and arguments)
     pub fn short_command(&self) -> &str {
-        self.command
-            .split_whitespace()
-            .next()
-            .and_then(|cmd| cmd.split('/').next_back())
-            .unwrap_or(&self.name)
+        short_command(&self.command, &self.name)
     }
 }
 
+///Program of a command line, without path and arguments, or `name` when there is none
+pub fn short_command<'a>(command: &'a str, name: &'a str) -> &'a str {
+    command
+        .split_whitespace()
+        .next()
+        .and_then(|cmd| cmd.split('/').next_back())
+        .unwrap_or(name)
+}
+
 impl Default for ProcessInfo {
     fn default() -> Self {
         Self::new()
@@ -205,4 +307,188 @@ pub enum SortBy {
     Pid,
     Name,
     Command,
+    ///Oldest first
+    Start,
+    ///Youngest first
+    Age,
+    ///Cumulative cpu time
+    Time,
+    MinorFaults,
+    MajorFaults,
+    VoluntarySwitches,
+    InvoluntarySwitches,
+    ///Next victim of the OOM killer last
+    OomScore,
+    OomScoreAdj,
+}
+
+impl SortBy {
+    ///Sortable columns in the order they appear in the table
+    pub const ALL: [SortBy; 14] = [
+        SortBy::Pid,
+        SortBy::Name,
+        SortBy::Cpu,
+        SortBy::Memory,
+        SortBy::Start,
+        SortBy::Age,
+        SortBy::Time,
+        SortBy::MinorFaults,
+        SortBy::MajorFaults,
+        SortBy::VoluntarySwitches,
+        SortBy::InvoluntarySwitches,
+        SortBy::OomScore,
+        SortBy::OomScoreAdj,
+        SortBy::Command,
+    ];
+
+    ///What the column holds, in words
+    pub fn title(&self) -> &'static str {
+        match self {
+            SortBy::Cpu => "cpu usage",
+            SortBy::Memory => "memory",
+            SortBy::Pid => "pid",
+            SortBy::Name => "name",
+            SortBy::Command => "command",
+            SortBy::Start => "start time",
+            SortBy::Age => "age",
+            SortBy::Time => "cpu time",
+            SortBy::MinorFaults => "minor faults",
+            SortBy::MajorFaults => "major faults",
+            SortBy::VoluntarySwitches => "voluntary context switches",
+            SortBy::InvoluntarySwitches => "involuntary context switches",
+            SortBy::OomScore => "oom score",
+            SortBy::OomScoreAdj => "oom score adjustment",
+        }
+    }
+
+    ///The activity rate this sorts on, for the page fault and context switch columns
+    pub fn activity_rate(&self, rates: &ActivityRates) -> Option<f64> {
+        match self {
+            SortBy::MinorFaults => Some(rates.minor_faults),
+            SortBy::MajorFaults => Some(rates.major_faults),
+            SortBy::VoluntarySwitches => Some(rates.voluntary_switches),
+            SortBy::InvoluntarySwitches => Some(rates.involuntary_switches),
+            _ => None,
+        }
+    }
+
+    ///The sortable column `offset` places along from this one, wrapping around
+    pub fn cycle(&self, offset: isize) -> SortBy {
+        let len = Self::ALL.len() as isize;
+        let idx = Self::ALL.iter().position(|s| s == self).unwrap_or(0) as isize;
+        Self::ALL[(idx + offset).rem_euclid(len) as usize].clone()
+    }
+}
+
+///A process an action is aimed at. Pids get reused, the start time tells apart
+///a new process that got the same pid since it was picked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Target {
+    pub pid: u64,
+    pub start_time: u64,
+}
+
+impl Target {
+    pub fn of(process: &ProcessInfo) -> Self {
+        Self {
+            pid: process.pid,
+            start_time: process.start_time,
+        }
+    }
+
+    ///Whether `process` is the one this target was picked as
+    pub fn is(&self, process: &ProcessInfo) -> bool {
+        process.pid == self.pid && process.start_time == self.start_time
+    }
+}
+
+///What processes are collapsed on in aggregate mode
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupBy {
+    Command,
+    Executable,
+}
+
+///Cumulative page faults (from stat) and context switches (from status) of a process
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActivityCounters {
+    pub minor_faults: u64,
+    ///Faults that had to go to disk, a sign of memory pressure
+    pub major_faults: u64,
+    pub voluntary_switches: u64,
+    ///Times the process was preempted, a sign of cpu contention
+    pub involuntary_switches: u64,
+}
+
+///Per second rates of `ActivityCounters`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActivityRates {
+    pub minor_faults: f64,
+    pub major_faults: f64,
+    pub voluntary_switches: f64,
+    pub involuntary_switches: f64,
+}
+
+impl ActivityRates {
+    ///Rates over `seconds` between two measurements of the counters
+    pub fn between(old: &ActivityCounters, new: &ActivityCounters, seconds: f64) -> Self {
+        let rate = |old: u64, new: u64| new.saturating_sub(old) as f64 / seconds;
+        Self {
+            minor_faults: rate(old.minor_faults, new.minor_faults),
+            major_faults: rate(old.major_faults, new.major_faults),
+            voluntary_switches: rate(old.voluntary_switches, new.voluntary_switches),
+            involuntary_switches: rate(old.involuntary_switches, new.involuntary_switches),
+        }
+    }
+
+    pub fn add(&mut self, other: &ActivityRates) {
+        self.minor_faults += other.minor_faults;
+        self.major_faults += other.major_faults;
+        self.voluntary_switches += other.voluntary_switches;
+        self.involuntary_switches += other.involuntary_switches;
+    }
+}
+
+///A set of processes sharing the same command or executable
+#[derive(Debug, Clone)]
+pub struct ProcessGroup {
+    pub key: String,
+    ///Indices into the process list, in display order
+    pub members: Vec<usize>,
+    pub cpu_percent: f64,
+    pub memory_kb: u64,
+    pub activity_rates: ActivityRates,
+}
+
+impl ProcessGroup {
+    pub fn new(key: String) -> Self {
+        Self {
+            key,
+            members: Vec::new(),
+            cpu_percent: 0.0,
+            memory_kb: 0,
+            activity_rates: ActivityRates::default(),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.members.len()
+    }
+
+    ///The largest `value` among the members, the group is as much at risk as its likeliest victim
+    pub fn max_member(&self, processes: &[ProcessInfo], value: fn(&ProcessInfo) -> i64) -> i64 {
+        self.members
+            .iter()
+            .map(|&i| value(&processes[i]))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+///Append a sample to a sparkline history, dropping the oldest once full
+pub fn push_history(history: &mut VecDeque<u64>, value: u64) {
+    if history.len() >= HISTORY_LEN {
+        history.pop_front();
+    }
+    history.push_back(value);
 }
@@ -0,0 +1,169 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Instant,
+};
+
+use anyhow::{Result, anyhow};
+
+use crate::model::push_history;
+
+///Cumulative counters of one interface, as found in `/proc/net/dev`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InterfaceCounters {
+    pub name: String,
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_drops: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_drops: u64,
+}
+
+///Per second rates of one interface, with recent throughput history
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceStats {
+    pub name: String,
+    pub rx_bytes_per_sec: f64,
+    pub rx_packets_per_sec: f64,
+    pub rx_errors_per_sec: f64,
+    pub rx_drops_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+    pub tx_packets_per_sec: f64,
+    pub tx_errors_per_sec: f64,
+    pub tx_drops_per_sec: f64,
+    pub rx_history: VecDeque<u64>,
+    pub tx_history: VecDeque<u64>,
+}
+
+///Parse the contents of `/proc/net/dev`
+pub fn parse_net_dev(content: &str) -> Result<Vec<InterfaceCounters>> {
+    let mut interfaces = Vec::new();
+    //two header lines
+    for line in content.lines().skip(2) {
+        let (name, counters) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("No interface name in '{line}'"))?;
+        let fields: Vec<u64> = counters
+            .split_whitespace()
+            .map(|f| f.parse::<u64>())
+            .collect::<Result<_, _>>()?;
+        if fields.len() < 16 {
+            return Err(anyhow!(
+                "Insufficient fields for {}: got {}, need 16",
+                name.trim(),
+                fields.len()
+            ));
+        }
+        interfaces.push(InterfaceCounters {
+            name: name.trim().to_string(),
+            rx_bytes: fields[0],
+            rx_packets: fields[1],
+            rx_errors: fields[2],
+            rx_drops: fields[3],
+            tx_bytes: fields[8],
+            tx_packets: fields[9],
+            tx_errors: fields[10],
+            tx_drops: fields[11],
+        });
+    }
+    Ok(interfaces)
+}
+
+///Turns successive `/proc/net/dev` readings into rates
+#[derive(Debug, Default)]
+pub struct NetworkMonitor {
+    last: HashMap<String, InterfaceCounters>,
+    last_sample: Option<Instant>,
+    pub interfaces: Vec<InterfaceStats>,
+}
+
+impl NetworkMonitor {
+    pub fn update(&mut self) -> Result<()> {
+        let content = std::fs::read_to_string("/proc/net/dev")?;
+        self.record(parse_net_dev(&content)?, Instant::now());
+        Ok(())
+    }
+
+    fn record(&mut self, counters: Vec<InterfaceCounters>, now: Instant) {
+        let elapsed = self
+            .last_sample
+            .map(|last| now.duration_since(last).as_secs_f64())
+            .unwrap_or(0.0);
+        let mut interfaces = Vec::with_capacity(counters.len());
+        for current in &counters {
+            let mut stats = self
+                .interfaces
+                .iter()
+                .find(|i| i.name == current.name)
+                .cloned()
+                .unwrap_or_else(|| InterfaceStats {
+                    name: current.name.clone(),
+                    ..InterfaceStats::default()
+                });
+            if let Some(previous) = self.last.get(&current.name)
+                && elapsed > 0.0
+            {
+                //counters go backwards when an interface is recreated
+                let rate = |now: u64, before: u64| now.saturating_sub(before) as f64 / elapsed;
+                stats.rx_bytes_per_sec = rate(current.rx_bytes, previous.rx_bytes);
+                stats.rx_packets_per_sec = rate(current.rx_packets, previous.rx_packets);
+                stats.rx_errors_per_sec = rate(current.rx_errors, previous.rx_errors);
+                stats.rx_drops_per_sec = rate(current.rx_drops, previous.rx_drops);
+                stats.tx_bytes_per_sec = rate(current.tx_bytes, previous.tx_bytes);
+                stats.tx_packets_per_sec = rate(current.tx_packets, previous.tx_packets);
+                stats.tx_errors_per_sec = rate(current.tx_errors, previous.tx_errors);
+                stats.tx_drops_per_sec = rate(current.tx_drops, previous.tx_drops);
+                push_history(&mut stats.rx_history, stats.rx_bytes_per_sec as u64);
+                push_history(&mut stats.tx_history, stats.tx_bytes_per_sec as u64);
+            }
+            interfaces.push(stats);
+        }
+        interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+        self.interfaces = interfaces;
+        self.last = counters.into_iter().map(|c| (c.name.clone(), c)).collect();
+        self.last_sample = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    const NET_DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  1000      10    0    0    0     0          0         0     1000      10    0    0    0     0       0          0
+  eth0: 50000     100    1    2    0     0          0         0    20000      80    0    3    0     0       0          0
+";
+
+    #[test]
+    pub fn test_parse_net_dev() -> Result<()> {
+        let interfaces = parse_net_dev(NET_DEV)?;
+        assert_eq!(interfaces.len(), 2);
+        assert_eq!(interfaces[1].name, "eth0");
+        assert_eq!(interfaces[1].rx_bytes, 50000);
+        assert_eq!(interfaces[1].rx_drops, 2);
+        assert_eq!(interfaces[1].tx_packets, 80);
+        assert_eq!(interfaces[1].tx_drops, 3);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_rates() -> Result<()> {
+        let mut monitor = NetworkMonitor::default();
+        let start = Instant::now();
+        monitor.record(parse_net_dev(NET_DEV)?, start);
+        let mut later = parse_net_dev(NET_DEV)?;
+        later[1].rx_bytes += 4000;
+        monitor.record(later, start + Duration::from_secs(2));
+        assert_eq!(monitor.interfaces[0].name, "eth0");
+        assert_eq!(monitor.interfaces[0].rx_bytes_per_sec, 2000.0);
+        assert_eq!(monitor.interfaces[0].tx_bytes_per_sec, 0.0);
+        assert_eq!(monitor.interfaces[0].rx_history.len(), 1);
+        Ok(())
+    }
+}
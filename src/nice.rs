@@ -0,0 +1,55 @@
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::processes::get_thread_ids;
+
+///Range of nice values, -20 runs first
+pub const NICE_RANGE: std::ops::RangeInclusive<i32> = -20..=19;
+
+///Parse a nice value typed by the user
+pub fn parse_nice(input: &str) -> Result<i32> {
+    let input = input.trim();
+    let nice = input
+        .parse::<i32>()
+        .with_context(|| format!("'{input}' is not a number"))?;
+    if !NICE_RANGE.contains(&nice) {
+        bail!("Nice goes from -20 to 19");
+    }
+    Ok(nice)
+}
+
+///Renice every thread of a process, setpriority only changes the one it is given.
+///Returns how many threads were reniced, lowering the value needs CAP_SYS_NICE.
+pub fn set_nice(pid: u64, nice: i32) -> Result<usize> {
+    let mut reniced = 0;
+    for tid in get_thread_ids(pid)? {
+        //SAFETY: setpriority only takes integers, a tid that is gone fails with ESRCH
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) } == 0 {
+            reniced += 1;
+            continue;
+        }
+        let error = std::io::Error::last_os_error();
+        //the thread exited while we were at it
+        if error.raw_os_error() != Some(libc::ESRCH) {
+            return Err(anyhow!("Cannot renice thread {tid}: {error}"));
+        }
+    }
+    Ok(reniced)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_nice() -> Result<()> {
+        assert_eq!(parse_nice(" -5 ")?, -5);
+        assert!(parse_nice("20").is_err());
+        assert!(parse_nice("low").is_err());
+
+        //keeping our own nice value never needs privileges
+        //SAFETY: getpriority only takes integers, 0 is the calling process
+        let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+        assert!(set_nice(std::process::id() as u64, nice)? >= 1);
+        Ok(())
+    }
+}
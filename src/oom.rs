@@ -0,0 +1,52 @@
+use anyhow::{Context, Result, bail};
+
+///Range the kernel accepts for `oom_score_adj`, -1000 exempts a process from the OOM killer
+pub const OOM_SCORE_ADJ_RANGE: std::ops::RangeInclusive<i64> = -1000..=1000;
+
+///Read a number from `/proc/<pid>/<file>`, such as `oom_score` or `oom_score_adj`
+pub fn read_oom_value(pid: u64, file: &str) -> Option<i64> {
+    std::fs::read_to_string(format!("/proc/{pid}/{file}"))
+        .ok()?
+        .trim()
+        .parse::<i64>()
+        .ok()
+}
+
+///Parse an `oom_score_adj` typed by the user
+pub fn parse_oom_score_adj(input: &str) -> Result<i64> {
+    let input = input.trim();
+    let adj = input
+        .parse::<i64>()
+        .with_context(|| format!("'{input}' is not a number"))?;
+    if !OOM_SCORE_ADJ_RANGE.contains(&adj) {
+        bail!("oom_score_adj goes from -1000 to 1000");
+    }
+    Ok(adj)
+}
+
+///Write `oom_score_adj`, lowering it below what it was needs CAP_SYS_RESOURCE
+pub fn set_oom_score_adj(pid: u64, adj: i64) -> Result<()> {
+    std::fs::write(format!("/proc/{pid}/oom_score_adj"), adj.to_string())
+        .with_context(|| format!("Cannot set oom_score_adj of {pid}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_oom_score_adj() -> Result<()> {
+        assert_eq!(parse_oom_score_adj(" -500 ")?, -500);
+        assert!(parse_oom_score_adj("1001").is_err());
+        assert!(parse_oom_score_adj("lots").is_err());
+
+        //raising our own score never needs privileges
+        let pid = std::process::id() as u64;
+        let adj = read_oom_value(pid, "oom_score_adj").unwrap_or(0);
+        if adj < 1000 {
+            set_oom_score_adj(pid, adj)?;
+        }
+        assert!(read_oom_value(pid, "oom_score").is_some());
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, collections::VecDeque};
 
 use anyhow::Result;
+use chrono::{DateTime, Datelike, Local};
 
 use crate::model::{ProcessInfo, SortBy};
 #[allow(dead_code)]
@@ -69,6 +70,20 @@ pub fn display_processes_sorted(
                 processes
                     .sort_by(|a, b| b.command.partial_cmp(&a.command).unwrap_or(Ordering::Equal));
             }
+            SortBy::Start => processes.sort_by_key(|p| std::cmp::Reverse(p.start_time)),
+            SortBy::Age => processes.sort_by_key(|p| p.start_time),
+            SortBy::Time => processes.sort_by_key(|p| std::cmp::Reverse(p.cpu_time_total)),
+            SortBy::OomScore => processes.sort_by_key(|p| std::cmp::Reverse(p.oom_score)),
+            SortBy::OomScoreAdj => processes.sort_by_key(|p| std::cmp::Reverse(p.oom_score_adj)),
+            SortBy::MinorFaults
+            | SortBy::MajorFaults
+            | SortBy::VoluntarySwitches
+            | SortBy::InvoluntarySwitches => processes.sort_by(|a, b| {
+                sort_by
+                    .activity_rate(&b.activity_rates)
+                    .partial_cmp(&sort_by.activity_rate(&a.activity_rates))
+                    .unwrap_or(Ordering::Equal)
+            }),
         }
     }
     display_processes(processes)
@@ -99,6 +114,89 @@ pub fn format_memory(kb: u64) -> String {
     format!("{:.1}{}", size, UNITS[unit_index])
 }
 
+/// Helper function to format a duration compactly, e.g. "45s", "12m03s", "3h07m", "2d04h"
+pub fn format_duration(seconds: u64) -> String {
+    let (days, hours, minutes, secs) = (
+        seconds / 86400,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+    );
+    if days > 0 {
+        format!("{days}d{hours:02}h")
+    } else if hours > 0 {
+        format!("{hours}h{minutes:02}m")
+    } else if minutes > 0 {
+        format!("{minutes}m{secs:02}s")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+/// Helper function to format cpu time like top's TIME+, "1:02.53" or "3h25:07"
+pub fn format_cpu_time(seconds: f64) -> String {
+    let hundredths = (seconds * 100.0) as u64;
+    let (hours, minutes, secs, fraction) = (
+        hundredths / 360000,
+        hundredths % 360000 / 6000,
+        hundredths % 6000 / 100,
+        hundredths % 100,
+    );
+    if hours > 0 {
+        format!("{hours}h{minutes:02}:{secs:02}")
+    } else {
+        format!("{minutes}:{secs:02}.{fraction:02}")
+    }
+}
+
+/// Helper function to format a start time like ps, the time for today and the date before that
+pub fn format_start_time(started: &DateTime<Local>) -> String {
+    let now = Local::now();
+    if started.date_naive() == now.date_naive() {
+        started.format("%H:%M").to_string()
+    } else if started.year() == now.year() {
+        started.format("%b%d").to_string()
+    } else {
+        started.format("%Y").to_string()
+    }
+}
+
+/// Helper function to format events per second compactly, e.g. "0", "37", "12k"
+pub fn format_count_rate(per_sec: f64) -> String {
+    if per_sec < 0.5 {
+        "0".to_string()
+    } else if per_sec < 10_000.0 {
+        format!("{per_sec:.0}")
+    } else if per_sec < 10_000_000.0 {
+        format!("{:.0}k", per_sec / 1000.0)
+    } else {
+        format!("{:.0}M", per_sec / 1_000_000.0)
+    }
+}
+
+/// Helper function to format a throughput, e.g. "1.2M/s"
+pub fn format_rate(bytes_per_sec: f64) -> String {
+    if bytes_per_sec < 1024.0 {
+        return format!("{:.0}B/s", bytes_per_sec);
+    }
+    format!("{}/s", format_memory(bytes_per_sec as u64 / 1024))
+}
+
+/// Render the last `width` values as a unicode sparkline, scaled to the largest of them
+pub fn sparkline(values: &VecDeque<u64>, width: usize) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let recent: Vec<u64> = values
+        .iter()
+        .skip(values.len().saturating_sub(width))
+        .copied()
+        .collect();
+    let max = recent.iter().copied().max().unwrap_or(0).max(1);
+    recent
+        .iter()
+        .map(|&v| BARS[((v as f64 / max as f64) * (BARS.len() - 1) as f64).round() as usize])
+        .collect()
+}
+
 // Helper function to truncate strings to fit in columns
 fn truncate_string(s: &str, max_len: usize) -> String {
     if s.len() <= max_len {
@@ -123,4 +221,18 @@ mod test {
         display_processes(&processes)?;
         Ok(())
     }
+
+    #[test]
+    pub fn test_format_cpu_time() {
+        assert_eq!(format_cpu_time(0.0), "0:00.00");
+        assert_eq!(format_cpu_time(62.53), "1:02.53");
+        assert_eq!(format_cpu_time(12307.0), "3h25:07");
+    }
+
+    #[test]
+    pub fn test_format_count_rate() {
+        assert_eq!(format_count_rate(0.2), "0");
+        assert_eq!(format_count_rate(37.4), "37");
+        assert_eq!(format_count_rate(12_345.0), "12k");
+    }
 }
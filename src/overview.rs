@@ -0,0 +1,220 @@
+use std::cmp::Ordering;
+
+use crate::{model::ProcessInfo, remote::Remote};
+
+///Which processes the table shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HostView {
+    ///This host's, read from /proc
+    Local,
+    ///No table, a row per agent we watch
+    Overview,
+    ///One agent's, by index into `App::remotes`
+    Host(usize),
+    ///Every agent's in one table, with a host column
+    Merged,
+}
+
+impl HostView {
+    pub fn is_remote(&self) -> bool {
+        *self != HostView::Local
+    }
+
+    ///Whether the table shows processes of the agent at `index`
+    pub fn shows(&self, index: usize) -> bool {
+        match self {
+            HostView::Local | HostView::Overview => false,
+            HostView::Host(i) => *i == index,
+            HostView::Merged => true,
+        }
+    }
+}
+
+///Order of the host overview, the busiest first for the numbers
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HostSort {
+    ///As given on the command line
+    #[default]
+    Given,
+    Name,
+    Cpu,
+    Memory,
+    Load,
+}
+
+impl HostSort {
+    pub fn title(&self) -> &'static str {
+        match self {
+            HostSort::Given => "as given",
+            HostSort::Name => "name",
+            HostSort::Cpu => "cpu",
+            HostSort::Memory => "memory",
+            HostSort::Load => "load",
+        }
+    }
+}
+
+///One row of the host overview
+#[derive(Debug, Clone)]
+pub struct HostSummary {
+    ///Index into `App::remotes`
+    pub index: usize,
+    pub hostname: String,
+    ///"up", "up, read only", "connecting" or why we are not connected
+    pub status: String,
+    pub connected: bool,
+    pub cpu_percent: f64,
+    pub cpu_count: usize,
+    pub memory_percent: f64,
+    pub memory_total_kb: u64,
+    pub load_average: [f64; 3],
+    pub tasks: usize,
+    ///Busiest process by cpu
+    pub top: Option<ProcessInfo>,
+}
+
+///Name of the agent at `index` in tables, with its endpoint when another agent has the same
+///name, as two agents on one machine or hosts named alike in different places do
+pub fn host_label(remotes: &[Remote], index: usize) -> String {
+    let remote = &remotes[index];
+    match remotes
+        .iter()
+        .filter(|r| r.hostname == remote.hostname)
+        .count()
+    {
+        1 => remote.hostname.clone(),
+        _ => format!("{}@{}", remote.hostname, remote.endpoint),
+    }
+}
+
+impl HostSummary {
+    pub fn of(remotes: &[Remote], index: usize) -> Self {
+        let remote = &remotes[index];
+        let status = match (&remote.error, remote.connected) {
+            (_, true) if remote.actions => "up".to_string(),
+            (_, true) => "up, read only".to_string(),
+            (Some(e), false) => e.clone(),
+            (None, false) => "connecting".to_string(),
+        };
+        let mut summary = Self {
+            index,
+            hostname: host_label(remotes, index),
+            status,
+            connected: remote.connected,
+            cpu_percent: 0.0,
+            cpu_count: 0,
+            memory_percent: 0.0,
+            memory_total_kb: 0,
+            load_average: [0.0; 3],
+            tasks: 0,
+            top: None,
+        };
+        if let Some(snapshot) = &remote.snapshot {
+            let system = &snapshot.system;
+            summary.cpu_percent = system.cpu_percent;
+            summary.cpu_count = system.cpu_count;
+            summary.memory_percent = system.memory_used_percent();
+            summary.memory_total_kb = system.memory_total_kb;
+            summary.load_average = system.load_average;
+            summary.tasks = system.tasks;
+            summary.top = snapshot
+                .processes
+                .iter()
+                .max_by(|a, b| {
+                    a.cpu_percent
+                        .partial_cmp(&b.cpu_percent)
+                        .unwrap_or(Ordering::Equal)
+                })
+                .cloned();
+        }
+        summary
+    }
+}
+
+///A row per agent in `sort` order, hosts we have no numbers for yet last
+pub fn summarize(remotes: &[Remote], sort: HostSort) -> Vec<HostSummary> {
+    let mut summaries: Vec<HostSummary> = (0..remotes.len())
+        .map(|i| HostSummary::of(remotes, i))
+        .collect();
+    let descending = |a: f64, b: f64| b.partial_cmp(&a).unwrap_or(Ordering::Equal);
+    let reported = |summary: &HostSummary| remotes[summary.index].snapshot.is_some();
+    //stable, so ties keep the order they were given in
+    summaries.sort_by(|a, b| {
+        reported(b).cmp(&reported(a)).then_with(|| match sort {
+            HostSort::Given => Ordering::Equal,
+            HostSort::Name => a.hostname.cmp(&b.hostname),
+            HostSort::Cpu => descending(a.cpu_percent, b.cpu_percent),
+            HostSort::Memory => descending(a.memory_percent, b.memory_percent),
+            HostSort::Load => descending(a.load_average[0], b.load_average[0]),
+        })
+    });
+    summaries
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        model::HostClock,
+        system::{Snapshot, SystemStats},
+    };
+
+    fn host(name: &str, cpu_percent: f64, load: f64, top: (&str, f64)) -> Remote {
+        let mut process = ProcessInfo::new();
+        process.pid = 1;
+        process.name = top.0.to_string();
+        process.cpu_percent = top.1;
+        let mut idle = ProcessInfo::new();
+        idle.pid = 2;
+        Remote::offline(
+            name,
+            Snapshot {
+                timestamp: 0,
+                clock: HostClock {
+                    boot_time: 0,
+                    clock_ticks: 100.0,
+                },
+                system: SystemStats {
+                    hostname: name.to_string(),
+                    cpu_percent,
+                    load_average: [load, 0.0, 0.0],
+                    memory_total_kb: 1000,
+                    memory_available_kb: 250,
+                    ..SystemStats::default()
+                },
+                processes: vec![idle, process],
+            },
+        )
+    }
+
+    #[test]
+    pub fn test_summarize() {
+        let remotes = vec![
+            host("build1", 10.0, 4.0, ("make", 9.0)),
+            host("build2", 95.0, 1.0, ("rustc", 90.0)),
+        ];
+        let busiest = summarize(&remotes, HostSort::Cpu);
+        assert_eq!(busiest[0].hostname, "build2");
+        assert_eq!(busiest[0].index, 1);
+        assert_eq!(
+            busiest[0].top.as_ref().map(|p| p.name.as_str()),
+            Some("rustc")
+        );
+        assert_eq!(busiest[0].memory_percent, 75.0);
+        assert_eq!(summarize(&remotes, HostSort::Load)[0].hostname, "build1");
+        assert_eq!(summarize(&remotes, HostSort::Given)[0].hostname, "build1");
+        let mut waiting = host("a-new-one", 0.0, 0.0, ("a", 0.0));
+        waiting.snapshot = None;
+        let remotes = vec![waiting, host("build1", 10.0, 4.0, ("make", 9.0))];
+        for sort in [HostSort::Given, HostSort::Name, HostSort::Cpu] {
+            assert_eq!(summarize(&remotes, sort)[1].hostname, "a-new-one");
+        }
+        let twins = vec![
+            host("vm", 0.0, 0.0, ("a", 0.0)),
+            host("vm", 0.0, 0.0, ("b", 0.0)),
+        ];
+        assert_eq!(host_label(&twins, 1), "vm@vm:8687");
+        assert!(HostView::Merged.shows(1));
+        assert!(!HostView::Host(0).shows(1));
+    }
+}
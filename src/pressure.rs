@@ -0,0 +1,134 @@
+use std::{collections::VecDeque, path::Path};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::model::push_history;
+
+///Where the unified (v2) cgroup hierarchy can be mounted
+const CGROUP2_ROOTS: [&str; 2] = ["/sys/fs/cgroup/unified", "/sys/fs/cgroup"];
+
+///Percentage of time tasks were stalled, averaged over 10s, 60s and 300s
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PressureAverages {
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+}
+
+///One resource's pressure: `some` tasks stalled, or `full`y stalled (not reported for cpu on old kernels)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pressure {
+    pub some: PressureAverages,
+    pub full: Option<PressureAverages>,
+}
+
+///Parse a pressure file such as `/proc/pressure/io` or a cgroup's `io.pressure`
+pub fn parse_pressure(content: &str) -> Result<Pressure> {
+    let mut some = None;
+    let mut full = None;
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let kind = fields.next();
+        let mut averages = PressureAverages::default();
+        for field in fields {
+            let Some((key, value)) = field.split_once('=') else {
+                continue;
+            };
+            match key {
+                "avg10" => averages.avg10 = value.parse()?,
+                "avg60" => averages.avg60 = value.parse()?,
+                "avg300" => averages.avg300 = value.parse()?,
+                _ => {}
+            }
+        }
+        match kind {
+            Some("some") => some = Some(averages),
+            Some("full") => full = Some(averages),
+            _ => {}
+        }
+    }
+    Ok(Pressure {
+        some: some.ok_or_else(|| anyhow!("No 'some' line in pressure file"))?,
+        full,
+    })
+}
+
+///Pressure for cpu, memory and io read from the files in `dir`, named with `suffix`
+fn read_pressure_set(dir: &Path, suffix: &str) -> Result<[Pressure; 3]> {
+    let read = |resource: &str| -> Result<Pressure> {
+        parse_pressure(&std::fs::read_to_string(
+            dir.join(format!("{resource}{suffix}")),
+        )?)
+    };
+    Ok([read("cpu")?, read("memory")?, read("io")?])
+}
+
+///The process's cgroup in the unified hierarchy, e.g. "/system.slice/nginx.service"
+pub fn process_cgroup(pid: u64) -> Option<String> {
+    let content = std::fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| path.to_string())
+}
+
+///Pressure of a cgroup, fails without PSI or on a v1-only hierarchy
+pub fn read_cgroup_pressure(cgroup: &str) -> Result<[Pressure; 3]> {
+    let relative = cgroup.trim_start_matches('/');
+    CGROUP2_ROOTS
+        .iter()
+        .map(|root| Path::new(root).join(relative))
+        .find(|dir| dir.join("cpu.pressure").exists())
+        .ok_or_else(|| anyhow!("No pressure files for cgroup {cgroup}"))
+        .and_then(|dir| read_pressure_set(&dir, ".pressure"))
+}
+
+///System wide pressure with history, stays empty on kernels without PSI
+#[derive(Debug, Default)]
+pub struct PressureMonitor {
+    ///cpu, memory and io, `None` when the kernel doesn't support PSI
+    pub current: Option<[Pressure; 3]>,
+    ///avg10 of `some` for cpu, memory and io, in hundredths of a percent
+    pub history: [VecDeque<u64>; 3],
+}
+
+impl PressureMonitor {
+    pub const RESOURCES: [&'static str; 3] = ["cpu", "mem", "io"];
+
+    pub fn update(&mut self) {
+        self.record(read_pressure_set(Path::new("/proc/pressure"), "").ok());
+    }
+
+    ///Take a reading made elsewhere, e.g. by a remote agent
+    pub fn record(&mut self, current: Option<[Pressure; 3]>) {
+        self.current = current;
+        if let Some(current) = &self.current {
+            for (history, pressure) in self.history.iter_mut().zip(current) {
+                push_history(history, (pressure.some.avg10 * 100.0) as u64);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_pressure() -> Result<()> {
+        let pressure = parse_pressure(
+            "some avg10=3.16 avg60=2.67 avg300=2.32 total=19994177\n\
+             full avg10=0.50 avg60=0.00 avg300=0.00 total=0\n",
+        )?;
+        assert_eq!(pressure.some.avg10, 3.16);
+        assert_eq!(pressure.some.avg300, 2.32);
+        assert_eq!(pressure.full.map(|f| f.avg10), Some(0.5));
+
+        //cpu on kernels before 5.13 has no full line
+        let cpu = parse_pressure("some avg10=0.00 avg60=0.00 avg300=0.00 total=0\n")?;
+        assert_eq!(cpu.full, None);
+        assert!(parse_pressure("").is_err());
+        Ok(())
+    }
+}
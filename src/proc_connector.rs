@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+
+use anyhow::{Result, anyhow};
+
+use crate::events::{LifecycleEvent, LifecycleKind};
+use crate::processes::{get_clock_ticks, get_command_line};
+
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+
+const PROC_EVENT_FORK: u32 = 0x0000_0001;
+const PROC_EVENT_EXEC: u32 = 0x0000_0002;
+const PROC_EVENT_EXIT: u32 = 0x8000_0000;
+
+const NLMSG_HDRLEN: usize = 16;
+const CN_MSG_LEN: usize = 20;
+///Where `struct proc_event` starts in a message: nlmsghdr + cn_msg
+const PROC_EVENT_OFFSET: usize = NLMSG_HDRLEN + CN_MSG_LEN;
+///`event_data` follows `what`, `cpu` and `timestamp_ns`
+const EVENT_DATA_OFFSET: usize = PROC_EVENT_OFFSET + 16;
+
+///What the kernel told us, decoded from a `struct proc_event`
+#[derive(Debug, Clone, PartialEq)]
+pub enum KernelEvent {
+    Fork { parent_pid: u32, child_pid: u32 },
+    Exec { pid: u32 },
+    Exit { pid: u32, ppid: u32, status: u32 },
+}
+
+///Subscription to the kernel's netlink process connector, events arrive on a background thread.
+///Unlike polling `/proc` this sees every fork, exec and exit however short lived,
+///but subscribing needs CAP_NET_ADMIN.
+pub struct ProcConnector {
+    receiver: Receiver<LifecycleEvent>,
+}
+
+impl ProcConnector {
+    ///Subscribe to process events, fails when we are not privileged enough
+    pub fn start() -> Result<Self> {
+        let socket = open_socket()?;
+        let (sender, receiver) = channel();
+        thread::Builder::new()
+            .name("proc-connector".to_string())
+            .spawn(move || listen(socket, sender))?;
+        Ok(Self { receiver })
+    }
+
+    ///Everything that happened since the last call
+    pub fn drain(&self) -> Vec<LifecycleEvent> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+fn open_socket() -> Result<OwnedFd> {
+    // SAFETY: plain socket creation, the fd is owned from here on
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_CONNECTOR,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    // SAFETY: fd was just returned by socket() and nothing else owns it
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    // SAFETY: sockaddr_nl is plain old data, zeroed is a valid value
+    let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_pid = std::process::id();
+    addr.nl_groups = CN_IDX_PROC;
+    // SAFETY: addr is a valid sockaddr_nl and we pass its real size
+    let bound = unsafe {
+        libc::bind(
+            socket.as_raw_fd(),
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if bound < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    let message = listen_message();
+    // SAFETY: the buffer outlives the call and we pass its real length
+    let sent = unsafe {
+        libc::send(
+            socket.as_raw_fd(),
+            message.as_ptr() as *const libc::c_void,
+            message.len(),
+            0,
+        )
+    };
+    if sent < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(socket)
+}
+
+///nlmsghdr + cn_msg + PROC_CN_MCAST_LISTEN
+fn listen_message() -> Vec<u8> {
+    let total_len = (PROC_EVENT_OFFSET + 4) as u32;
+    let mut message = Vec::with_capacity(total_len as usize);
+    //nlmsghdr
+    message.extend_from_slice(&total_len.to_ne_bytes());
+    message.extend_from_slice(&(libc::NLMSG_DONE as u16).to_ne_bytes());
+    message.extend_from_slice(&0u16.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(&std::process::id().to_ne_bytes());
+    //cn_msg
+    message.extend_from_slice(&CN_IDX_PROC.to_ne_bytes());
+    message.extend_from_slice(&CN_VAL_PROC.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(&4u16.to_ne_bytes());
+    message.extend_from_slice(&0u16.to_ne_bytes());
+    //payload
+    message.extend_from_slice(&PROC_CN_MCAST_LISTEN.to_ne_bytes());
+    message
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    buf.get(offset..offset + 4)
+        .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap_or_default()))
+}
+
+///Decode a single netlink message, ignoring thread events and event types we don't track
+pub fn parse_message(buf: &[u8]) -> Option<KernelEvent> {
+    let what = read_u32(buf, PROC_EVENT_OFFSET)?;
+    let data = |i: usize| read_u32(buf, EVENT_DATA_OFFSET + i * 4);
+    match what {
+        PROC_EVENT_FORK => {
+            let (parent_pid, child_pid, child_tgid) = (data(1)?, data(2)?, data(3)?);
+            (child_pid == child_tgid).then_some(KernelEvent::Fork {
+                parent_pid,
+                child_pid,
+            })
+        }
+        PROC_EVENT_EXEC => Some(KernelEvent::Exec { pid: data(1)? }),
+        PROC_EVENT_EXIT => {
+            let (pid, tgid, status) = (data(0)?, data(1)?, data(2)?);
+            (pid == tgid).then_some(KernelEvent::Exit {
+                pid,
+                ppid: data(5)?,
+                status,
+            })
+        }
+        _ => None,
+    }
+}
+
+///Split a wait status into exit code and terminating signal
+pub fn decode_exit_status(status: u32) -> (Option<i32>, Option<i32>) {
+    let signal = (status & 0x7f) as i32;
+    if signal == 0 {
+        (Some(((status >> 8) & 0xff) as i32), None)
+    } else {
+        (None, Some(signal))
+    }
+}
+
+///What we remember about a live process so we can describe it when it exits
+struct Tracked {
+    name: String,
+    command: String,
+    forked_at: std::time::Instant,
+}
+
+fn listen(socket: OwnedFd, sender: Sender<LifecycleEvent>) {
+    let mut tracked: HashMap<u32, Tracked> = HashMap::new();
+    let mut buf = vec![0u8; 8192];
+    loop {
+        // SAFETY: buf outlives the call and we pass its real length
+        let len = unsafe {
+            libc::recv(
+                socket.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+            )
+        };
+        if len <= 0 {
+            let error = std::io::Error::last_os_error();
+            //ENOBUFS means the kernel dropped events because we fell behind, keep going
+            if error.kind() == std::io::ErrorKind::Interrupted
+                || error.raw_os_error() == Some(libc::ENOBUFS)
+            {
+                continue;
+            }
+            return;
+        }
+        let Some(kernel_event) = parse_message(&buf[..len as usize]) else {
+            continue;
+        };
+        let event = describe(kernel_event, &mut tracked);
+        if sender.send(event).is_err() {
+            //the app went away
+            return;
+        }
+    }
+}
+
+///Turn a kernel event into a lifecycle event, reading what we can from /proc while it's still there
+fn describe(kernel_event: KernelEvent, tracked: &mut HashMap<u32, Tracked>) -> LifecycleEvent {
+    match kernel_event {
+        KernelEvent::Fork {
+            parent_pid,
+            child_pid,
+        } => {
+            let (name, command) = read_identity(child_pid);
+            let event = LifecycleEvent {
+                ppid: parent_pid as u64,
+                parent: read_identity(parent_pid).0,
+                ..LifecycleEvent::blank(LifecycleKind::Started, child_pid as u64, &name, &command)
+            };
+            tracked.insert(
+                child_pid,
+                Tracked {
+                    name,
+                    command,
+                    forked_at: std::time::Instant::now(),
+                },
+            );
+            event
+        }
+        KernelEvent::Exec { pid } => {
+            let (name, command) = read_identity(pid);
+            let event = LifecycleEvent::blank(LifecycleKind::Exec, pid as u64, &name, &command);
+            let forked_at = tracked
+                .get(&pid)
+                .map(|t| t.forked_at)
+                .unwrap_or_else(std::time::Instant::now);
+            tracked.insert(
+                pid,
+                Tracked {
+                    name,
+                    command,
+                    forked_at,
+                },
+            );
+            event
+        }
+        KernelEvent::Exit { pid, ppid, status } => {
+            //the process is a zombie until reaped, so its stat is usually still readable
+            let stat = read_exit_stat(pid).ok();
+            let known = tracked.remove(&pid);
+            let (name, command) = match &known {
+                Some(t) => (t.name.clone(), t.command.clone()),
+                None => (
+                    stat.as_ref().map(|s| s.name.clone()).unwrap_or_default(),
+                    String::new(),
+                ),
+            };
+            let (exit_code, exit_signal) = decode_exit_status(status);
+            LifecycleEvent {
+                ppid: ppid as u64,
+                parent: read_identity(ppid).0,
+                start_time: stat.as_ref().map(|s| s.start_time),
+                lifetime_secs: known.map(|t| t.forked_at.elapsed().as_secs_f64()),
+                cpu_secs: stat.map(|s| s.cpu_ticks as f64 / get_clock_ticks()),
+                exit_code,
+                exit_signal,
+                ..LifecycleEvent::blank(LifecycleKind::Exited, pid as u64, &name, &command)
+            }
+        }
+    }
+}
+
+///Name and command line of a pid, empty when it is already gone
+fn read_identity(pid: u32) -> (String, String) {
+    let name = std::fs::read_to_string(format!("/proc/{pid}/comm"))
+        .map(|comm| comm.trim_end().to_string())
+        .unwrap_or_default();
+    let command = get_command_line(&pid.to_string()).unwrap_or_default();
+    (name, command)
+}
+
+///What the stat of an exiting process still tells us
+struct ExitStat {
+    name: String,
+    ///utime + stime
+    cpu_ticks: u64,
+    start_time: u64,
+}
+
+fn read_exit_stat(pid: u32) -> Result<ExitStat> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat"))?;
+    let start_paren = stat.find('(').ok_or_else(|| anyhow!("No name in stat"))?;
+    let end_paren = stat.rfind(')').ok_or_else(|| anyhow!("No name in stat"))?;
+    let fields: Vec<&str> = stat[end_paren + 2..].split_whitespace().collect();
+    let ticks = |i: usize| fields.get(i).and_then(|f| f.parse::<u64>().ok());
+    Ok(ExitStat {
+        name: stat[start_paren + 1..end_paren].to_string(),
+        cpu_ticks: ticks(11).unwrap_or(0) + ticks(12).unwrap_or(0),
+        start_time: ticks(19).ok_or_else(|| anyhow!("No start time in stat"))?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn message(what: u32, data: &[u32]) -> Vec<u8> {
+        let mut buf = vec![0u8; PROC_EVENT_OFFSET];
+        buf.extend_from_slice(&what.to_ne_bytes());
+        buf.extend_from_slice(&[0u8; 12]);
+        for value in data {
+            buf.extend_from_slice(&value.to_ne_bytes());
+        }
+        buf
+    }
+
+    #[test]
+    pub fn test_parse_message() {
+        let fork = message(PROC_EVENT_FORK, &[10, 10, 11, 11]);
+        assert_eq!(
+            parse_message(&fork),
+            Some(KernelEvent::Fork {
+                parent_pid: 10,
+                child_pid: 11
+            })
+        );
+        //a new thread, not a new process
+        let thread = message(PROC_EVENT_FORK, &[10, 10, 12, 10]);
+        assert_eq!(parse_message(&thread), None);
+        let exit = message(PROC_EVENT_EXIT, &[11, 11, 256, 17, 10, 10]);
+        assert_eq!(
+            parse_message(&exit),
+            Some(KernelEvent::Exit {
+                pid: 11,
+                ppid: 10,
+                status: 256
+            })
+        );
+        assert_eq!(parse_message(&exit[..PROC_EVENT_OFFSET + 2]), None);
+    }
+
+    #[test]
+    pub fn test_decode_exit_status() {
+        assert_eq!(decode_exit_status(0), (Some(0), None));
+        assert_eq!(decode_exit_status(1 << 8), (Some(1), None));
+        assert_eq!(decode_exit_status(9), (None, Some(9)));
+    }
+}
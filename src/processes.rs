@@ -1,14 +1,19 @@
+use std::path::Path;
 use std::sync::OnceLock;
 use std::time::Instant;
 
-use crate::model::ProcessInfo;
+use crate::kstack::read_wchan;
 use crate::model::ProcessState;
+use crate::model::{ActivityCounters, ActivityRates, ProcessInfo, Target};
+use crate::oom::read_oom_value;
+use crate::security::parse_security;
 use anyhow::Result;
 use anyhow::anyhow;
 use nix::unistd::{SysconfVar, sysconf};
 use users::{Users, UsersCache};
 
 static CLOCK_TICKS_PER_SEC: OnceLock<f64> = OnceLock::new();
+static BOOT_TIME: OnceLock<u64> = OnceLock::new();
 
 // static USER_CACHE: LazyLock<UsersCache> = LazyLock::new(|| UsersCache::new());
 
@@ -18,16 +23,24 @@ pub fn get_pids() -> Result<Vec<u64>> {
     //get the process ids by parsing the /proc/ directory
     for dir_entry in std::fs::read_dir("/proc")? {
         let entry = dir_entry?;
-        if let Some(name) = entry.file_name().to_str() {
-            if let Ok(pid) = name.parse::<u64>() {
-                pids.push(pid);
-            }
+        if let Some(name) = entry.file_name().to_str()
+            && let Ok(pid) = name.parse::<u64>()
+        {
+            pids.push(pid);
         }
     }
 
     Ok(pids)
 }
 
+///Ids of the threads of a process, from `/proc/<pid>/task`
+pub fn get_thread_ids(pid: u64) -> Result<Vec<u64>> {
+    Ok(std::fs::read_dir(format!("/proc/{pid}/task"))?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().to_str()?.parse::<u64>().ok())
+        .collect())
+}
+
 pub fn get_process_info(user_cache: &mut UsersCache) -> Result<Vec<ProcessInfo>> {
     let mut process_info_vec = Vec::new();
 
@@ -41,6 +54,32 @@ pub fn get_process_info(user_cache: &mut UsersCache) -> Result<Vec<ProcessInfo>>
     Ok(process_info_vec)
 }
 
+///Start time of a running process, in clock ticks after boot like `ProcessInfo::start_time`
+pub fn read_start_time(pid: u64) -> Result<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat"))?;
+    //the name can hold spaces and parentheses, count fields from the last ')'
+    let rest = &stat[stat
+        .rfind(')')
+        .ok_or_else(|| anyhow!("No closing parenthesis in stat"))?
+        + 1..];
+    Ok(rest
+        .split_whitespace()
+        .nth(19)
+        .ok_or_else(|| anyhow!("No start time in stat"))?
+        .parse::<u64>()?)
+}
+
+///Fails unless `target` still runs, as the same process and not a new one that reused its pid
+pub fn check_target(target: &Target) -> Result<()> {
+    match read_start_time(target.pid) {
+        Err(_) => Err(anyhow!("{} exited", target.pid)),
+        Ok(start_time) if start_time != target.start_time => {
+            Err(anyhow!("{} exited and its pid was reused", target.pid))
+        }
+        Ok(_) => Ok(()),
+    }
+}
+
 pub fn parse_process(pid: u64, user_cache: &mut UsersCache) -> Result<ProcessInfo> {
     let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid))?;
     // println!("Raw stat line: {stat}");
@@ -82,13 +121,40 @@ pub fn parse_process(pid: u64, user_cache: &mut UsersCache) -> Result<ProcessInf
     let start_time = stat_parts[19].parse::<u64>().unwrap_or(0);
     let vsize = stat_parts[20].parse::<u64>().unwrap_or(0);
     let _rss = stat_parts[21].parse::<u64>().unwrap_or(0);
+    //field 39, "processor"
+    let last_cpu = stat_parts.get(36).and_then(|cpu| cpu.parse::<u32>().ok());
 
     // Get additional info
     let command = get_command_line(&pid.to_string()).unwrap_or_else(|_| name.to_string());
-    let memory_kb = get_memory_usage(&pid.to_string()).unwrap_or(0);
+    let exe = get_executable(&pid.to_string()).unwrap_or_default();
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).unwrap_or_default();
+    let memory_kb = get_memory_usage(&status).unwrap_or(0);
+    let cpus_allowed = get_status_field(&status, "Cpus_allowed_list:")
+        .unwrap_or_default()
+        .to_string();
+    let status_number = |name: &str| {
+        get_status_field(&status, name)
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(0)
+    };
+    //the LSM label takes more reads, callers that show it fill it in
+    let security = parse_security(&status).unwrap_or_default();
+    let activity = ActivityCounters {
+        minor_faults: stat_parts[7].parse::<u64>().unwrap_or(0),
+        major_faults: stat_parts[9].parse::<u64>().unwrap_or(0),
+        voluntary_switches: status_number("voluntary_ctxt_switches:"),
+        involuntary_switches: status_number("nonvoluntary_ctxt_switches:"),
+    };
     let user =
         get_process_user(file_pid, user_cache).unwrap_or_else(|| format!("uid:{}", file_pid));
     let terminal = get_terminal_name(tty_nr);
+    //only blocked processes have a meaningful wait channel
+    let wchan = match state {
+        ProcessState::DiskSleep | ProcessState::Sleeping => {
+            read_wchan(Path::new(&format!("/proc/{pid}")))
+        }
+        _ => String::new(),
+    };
 
     let cpu_time_total = utime + stime;
     let virtual_memory_kb = vsize / 1024;
@@ -102,10 +168,20 @@ pub fn parse_process(pid: u64, user_cache: &mut UsersCache) -> Result<ProcessInf
         ppid,
         name: name.to_string(),
         command,
+        exe,
         cpu_percent,
         memory_kb,
         start_time,
         state,
+        wchan,
+        activity,
+        last_activity: None,
+        activity_rates: ActivityRates::default(),
+        security,
+        oom_score: read_oom_value(pid, "oom_score").unwrap_or(0),
+        oom_score_adj: read_oom_value(pid, "oom_score_adj").unwrap_or(0),
+        last_cpu,
+        cpus_allowed,
         user,
         priority,
         nice,
@@ -116,6 +192,8 @@ pub fn parse_process(pid: u64, user_cache: &mut UsersCache) -> Result<ProcessInf
         terminal,
         last_cpu_time,
         last_measurement,
+        host: None,
+        host_clock: None,
     })
 }
 
@@ -173,8 +251,7 @@ fn get_process_user(pid: u64, user_cache: &mut UsersCache) -> Option<String> {
     }
 }
 
-fn get_memory_usage(pid: &str) -> Option<u64> {
-    let status_content = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+fn get_memory_usage(status_content: &str) -> Option<u64> {
     for line in status_content.lines() {
         //VmRSS: 13484 kB
         if line.starts_with("VmRSS:") {
@@ -184,12 +261,29 @@ fn get_memory_usage(pid: &str) -> Option<u64> {
     None
 }
 
+///Value of a `/proc/<pid>/status` line such as "Cpus_allowed_list: 0-3"
+fn get_status_field<'a>(status_content: &'a str, name: &str) -> Option<&'a str> {
+    status_content
+        .lines()
+        .find_map(|line| line.strip_prefix(name))
+        .map(|value| value.trim())
+}
+
 pub fn get_command_line(pid: &str) -> Result<String> {
     let cmd = std::fs::read_to_string(format!("/proc/{pid}/cmdline"))?;
+    //arguments are nul separated, and the whole thing is nul terminated
+    let cmd = cmd.trim_end_matches('\0').replace('\0', " ");
 
     Ok(cmd)
 }
 
+///Path of the binary the process is running, only readable for our own processes unless root
+pub fn get_executable(pid: &str) -> Result<String> {
+    let exe = std::fs::read_link(format!("/proc/{pid}/exe"))?;
+
+    Ok(exe.to_string_lossy().to_string())
+}
+
 pub fn update_cpu_percent(proc_info: &mut ProcessInfo) {
     let current_time = Instant::now();
     let current_cpu_time = proc_info.cpu_time_total;
@@ -211,6 +305,33 @@ pub fn update_cpu_percent(proc_info: &mut ProcessInfo) {
     proc_info.last_measurement = Some(current_time);
 }
 
+///Page fault and context switch rates since the last measurement.
+///Call before `update_cpu_percent`, which moves `last_measurement` on.
+pub fn update_activity_rates(proc_info: &mut ProcessInfo) {
+    if let (Some(last_activity), Some(last_time)) =
+        (proc_info.last_activity, proc_info.last_measurement)
+    {
+        let time_delta = last_time.elapsed().as_secs_f64();
+        if time_delta > 0.0 {
+            proc_info.activity_rates =
+                ActivityRates::between(&last_activity, &proc_info.activity, time_delta);
+        }
+    }
+    proc_info.last_activity = Some(proc_info.activity);
+}
+
+///Seconds since boot, from `/proc/uptime`
+pub fn get_uptime() -> Result<f64> {
+    let uptime = std::fs::read_to_string("/proc/uptime")?;
+    let seconds = uptime
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("Empty /proc/uptime"))?
+        .parse::<f64>()?;
+
+    Ok(seconds)
+}
+
 pub fn get_clock_ticks() -> f64 {
     *CLOCK_TICKS_PER_SEC.get_or_init(|| match sysconf(SysconfVar::CLK_TCK) {
         Ok(Some(ticks)) => ticks as f64,
@@ -224,3 +345,66 @@ pub fn get_clock_ticks() -> f64 {
         }
     })
 }
+
+///Seconds since the epoch the system booted, the `btime` line of `/proc/stat`
+fn parse_boot_time(stat: &str) -> Option<u64> {
+    stat.lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|btime| btime.trim().parse::<u64>().ok())
+}
+
+///When the system booted, in seconds since the epoch. Process start times are relative to it.
+pub fn get_boot_time() -> u64 {
+    *BOOT_TIME.get_or_init(|| {
+        std::fs::read_to_string("/proc/stat")
+            .ok()
+            .and_then(|stat| parse_boot_time(&stat))
+            .unwrap_or_else(|| {
+                //work it out from the uptime instead
+                let now = chrono::Local::now().timestamp() as f64;
+                (now - get_uptime().unwrap_or(0.0)) as u64
+            })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_boot_time() {
+        let stat = "cpu  10132153 290696 3084719 46828483 16683 0 25195 0 0 0\n\
+                    intr 1462898 0 9 0\n\
+                    ctxt 115315\n\
+                    btime 1769781234\n\
+                    processes 2389\n";
+        assert_eq!(parse_boot_time(stat), Some(1769781234));
+        assert_eq!(parse_boot_time("cpu 1 2 3\n"), None);
+    }
+
+    #[test]
+    pub fn test_check_target() -> Result<()> {
+        let mut child = std::process::Command::new("sleep").arg("10").spawn()?;
+        let pid = child.id() as u64;
+        let target = Target {
+            pid,
+            start_time: read_start_time(pid)?,
+        };
+        assert!(check_target(&target).is_ok());
+        let reused = Target {
+            start_time: target.start_time + 1,
+            ..target
+        };
+        assert_eq!(
+            check_target(&reused).unwrap_err().to_string(),
+            format!("{pid} exited and its pid was reused")
+        );
+        child.kill()?;
+        child.wait()?;
+        assert_eq!(
+            check_target(&target).unwrap_err().to_string(),
+            format!("{pid} exited")
+        );
+        Ok(())
+    }
+}
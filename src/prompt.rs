@@ -0,0 +1,67 @@
+use crate::model::Target;
+
+///What to do with the text once the prompt is submitted
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromptAction {
+    ///Change a resource limit of a process, by its name in `/proc/<pid>/limits`
+    SetLimit { target: Target, limit: String },
+    ///Pin processes, or their threads, to a cpu list
+    SetAffinity { targets: Vec<Target> },
+    ///Make a process more or less likely to be picked by the OOM killer
+    SetOomScoreAdj { target: Target },
+    ///Send a signal to processes, by name or number, on `host` or this one
+    SendSignal {
+        host: Option<String>,
+        targets: Vec<Target>,
+    },
+    ///Change the nice value of processes, on `host` or this one
+    Renice {
+        host: Option<String>,
+        targets: Vec<Target>,
+    },
+    ///Select the row of a process
+    JumpToPid,
+    ///Only show processes matching a predicate, empty to show everything again
+    Filter,
+}
+
+impl PromptAction {
+    ///Processes a bulk action is about to change, empty for everything else
+    pub fn targets(&self) -> &[Target] {
+        match self {
+            PromptAction::SetAffinity { targets }
+            | PromptAction::SendSignal { targets, .. }
+            | PromptAction::Renice { targets, .. } => targets,
+            _ => &[],
+        }
+    }
+
+    ///Host the targets are on, None for this one
+    pub fn host(&self) -> Option<&str> {
+        match self {
+            PromptAction::SendSignal { host, .. } | PromptAction::Renice { host, .. } => {
+                host.as_deref()
+            }
+            _ => None,
+        }
+    }
+}
+
+///Single line of input shown in place of the menu
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prompt {
+    pub label: String,
+    pub input: String,
+    pub action: PromptAction,
+}
+
+impl Prompt {
+    ///A prompt with `input` already filled in, usually the current value
+    pub fn new(label: impl Into<String>, input: impl Into<String>, action: PromptAction) -> Self {
+        Self {
+            label: label.into(),
+            input: input.into(),
+            action,
+        }
+    }
+}
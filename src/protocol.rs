@@ -0,0 +1,307 @@
+use std::{
+    fmt::Display,
+    io::{BufRead, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    os::unix::{
+        fs::{FileTypeExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{model::Target, system::Snapshot};
+
+///Bumped whenever a message changes shape, agent and client have to speak the same one
+pub const PROTOCOL_VERSION: u32 = 2;
+
+///Longest line we read, a snapshot of a few thousand processes is a few MB
+pub const MAX_MESSAGE_LEN: u64 = 64 * 1024 * 1024;
+
+///Longest line we read before the client has said who it is
+pub const MAX_HELLO_LEN: u64 = 4096;
+
+///Longest action a client may send, a target takes about 40 bytes
+pub const MAX_ACTION_LEN: u64 = 16 * 1024 * 1024;
+
+///How long a client gets to say hello before it is dropped
+pub const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+///A client that stops reading for this long is dropped, rather than queueing snapshots for it
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+///From the client to the agent, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    ///First message on every connection
+    Hello {
+        version: u32,
+        token: Option<String>,
+    },
+    ///Send a signal, by name e.g. "SIGTERM", to processes.
+    ///The agent skips targets whose pid went to another process since the client saw them.
+    Signal {
+        targets: Vec<Target>,
+        signal: String,
+    },
+    Renice {
+        targets: Vec<Target>,
+        nice: i32,
+    },
+}
+
+///From the agent to the client, one JSON object per line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AgentMessage {
+    ///Answer to a hello the agent accepted
+    Welcome {
+        version: u32,
+        hostname: String,
+        ///Whether the agent takes signals and renices, it only does with a token
+        actions: bool,
+    },
+    Snapshot(Box<Snapshot>),
+    ///How an action went, for the status bar
+    ActionDone(String),
+    ActionFailed(String),
+    ///Why the agent is hanging up
+    Refused(String),
+}
+
+///`AgentMessage::Snapshot` from JSON the collector already serialized,
+///spelled out the way serde writes an enum so every client doesn't serialize it again
+pub fn snapshot_line(json: &str) -> String {
+    format!("{{\"Snapshot\":{json}}}\n")
+}
+
+pub fn write_message(writer: &mut impl Write, message: &impl Serialize) -> Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line)?;
+    writer.flush()?;
+    Ok(())
+}
+
+///Next message, None once the other end hung up
+pub fn read_message<T: DeserializeOwned>(
+    reader: &mut impl BufRead,
+    max_len: u64,
+) -> Result<Option<T>> {
+    let mut line = String::new();
+    let read = reader.take(max_len).read_line(&mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with('\n') {
+        return Err(match read as u64 >= max_len {
+            true => anyhow!("Message longer than {max_len} bytes"),
+            false => anyhow!("Connection closed mid message"),
+        });
+    }
+    Ok(Some(serde_json::from_str(&line).context("Bad message")?))
+}
+
+///Where an agent listens or a client connects: "host:port", or "unix:/path" for a Unix socket
+#[derive(Debug, Clone, PartialEq)]
+pub enum Endpoint {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl Endpoint {
+    pub fn parse(address: &str) -> Self {
+        match address.strip_prefix("unix:") {
+            Some(path) => Endpoint::Unix(PathBuf::from(path)),
+            None if address.starts_with('/') => Endpoint::Unix(PathBuf::from(address)),
+            None => Endpoint::Tcp(address.to_string()),
+        }
+    }
+
+    pub fn connect(&self) -> Result<Connection> {
+        let connection = match self {
+            Endpoint::Tcp(address) => TcpStream::connect(address).map(|stream| {
+                //snapshots are one write each, don't hold back the action replies
+                let _ = stream.set_nodelay(true);
+                Connection::Tcp(stream)
+            }),
+            Endpoint::Unix(path) => UnixStream::connect(path).map(Connection::Unix),
+        };
+        connection.with_context(|| format!("Cannot connect to {self}"))
+    }
+
+    pub fn listen(&self) -> Result<Listener> {
+        match self {
+            Endpoint::Tcp(address) => TcpListener::bind(address)
+                .map(Listener::Tcp)
+                .with_context(|| format!("Cannot listen on {self}")),
+            Endpoint::Unix(path) => {
+                //a socket left behind by an agent that died, nothing else is in the way
+                if std::fs::metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                    std::fs::remove_file(path)?;
+                }
+                let listener =
+                    UnixListener::bind(path).with_context(|| format!("Cannot listen on {self}"))?;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+                Ok(Listener::Unix(listener))
+            }
+        }
+    }
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endpoint::Tcp(address) => write!(f, "{address}"),
+            Endpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+impl Listener {
+    pub fn accept(&self) -> Result<Connection> {
+        Ok(match self {
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                let _ = stream.set_nodelay(true);
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                Connection::Tcp(stream)
+            }
+            Listener::Unix(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                Connection::Unix(stream)
+            }
+        })
+    }
+}
+
+///Either kind of stream, so the agent and client don't care which they got
+pub enum Connection {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Connection {
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(match self {
+            Connection::Tcp(stream) => Connection::Tcp(stream.try_clone()?),
+            Connection::Unix(stream) => Connection::Unix(stream.try_clone()?),
+        })
+    }
+
+    ///Make reads, on this and its clones, fail after `timeout` without data, None to wait forever
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.set_read_timeout(timeout)?,
+            Connection::Unix(stream) => stream.set_read_timeout(timeout)?,
+        }
+        Ok(())
+    }
+
+    ///Hang up both ways, which also stops whoever is reading from a clone
+    pub fn shutdown(&self) {
+        let _ = match self {
+            Connection::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            Connection::Unix(stream) => stream.shutdown(Shutdown::Both),
+        };
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Tcp(stream) => stream.read(buf),
+            Connection::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Tcp(stream) => stream.write(buf),
+            Connection::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.flush(),
+            Connection::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{model::HostClock, system::SystemStats};
+
+    #[test]
+    pub fn test_messages() -> Result<()> {
+        let mut buffer = Vec::new();
+        write_message(
+            &mut buffer,
+            &ClientMessage::Renice {
+                targets: vec![Target {
+                    pid: 1,
+                    start_time: 2,
+                }],
+                nice: 5,
+            },
+        )?;
+        let snapshot = Snapshot {
+            timestamp: 1,
+            clock: HostClock {
+                boot_time: 2,
+                clock_ticks: 100.0,
+            },
+            system: SystemStats::default(),
+            processes: Vec::new(),
+        };
+        buffer.extend(snapshot_line(&serde_json::to_string(&snapshot)?).as_bytes());
+        buffer.extend(b"{\"Renice\":");
+
+        let mut reader = buffer.as_slice();
+        assert_eq!(
+            read_message::<ClientMessage>(&mut reader, MAX_MESSAGE_LEN)?,
+            Some(ClientMessage::Renice {
+                targets: vec![Target {
+                    pid: 1,
+                    start_time: 2,
+                }],
+                nice: 5
+            })
+        );
+        match read_message::<AgentMessage>(&mut reader, MAX_MESSAGE_LEN)? {
+            Some(AgentMessage::Snapshot(received)) => assert_eq!(received.clock.boot_time, 2),
+            other => panic!("expected a snapshot, got {other:?}"),
+        }
+        //half a message is an error, nothing at all is the end
+        assert!(read_message::<ClientMessage>(&mut reader, MAX_MESSAGE_LEN).is_err());
+        assert!(read_message::<ClientMessage>(&mut reader, MAX_MESSAGE_LEN)?.is_none());
+        assert!(read_message::<ClientMessage>(&mut "x".repeat(100).as_bytes(), 10).is_err());
+
+        assert_eq!(
+            Endpoint::parse("unix:/run/rustop.sock"),
+            Endpoint::Unix(PathBuf::from("/run/rustop.sock"))
+        );
+        assert_eq!(
+            Endpoint::parse("/run/rustop.sock").to_string(),
+            "unix:/run/rustop.sock"
+        );
+        assert_eq!(
+            Endpoint::parse("build1:8687"),
+            Endpoint::Tcp("build1:8687".to_string())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    io::BufReader,
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, Sender, channel},
+    },
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::{
+    model::ProcessInfo,
+    protocol::{
+        AgentMessage, ClientMessage, Connection, Endpoint, MAX_MESSAGE_LEN, PROTOCOL_VERSION,
+        read_message, write_message,
+    },
+    system::Snapshot,
+};
+
+///How long to wait before trying a lost agent again
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+type AgentSender = Sender<Result<AgentMessage, String>>;
+
+///What came in from an agent since the last poll
+#[derive(Debug, Default)]
+pub struct RemoteUpdate {
+    ///A new snapshot arrived
+    pub fresh: bool,
+    ///Action results and connection news for the status bar
+    pub notices: Vec<String>,
+}
+
+///Connection to a `rustop agent`, read on a thread of its own that reconnects when it drops
+pub struct Remote {
+    pub endpoint: Endpoint,
+    ///Name the agent gave, the endpoint until it said hello
+    pub hostname: String,
+    ///Whether the agent takes signals and renices
+    pub actions: bool,
+    pub connected: bool,
+    ///Why we are not connected, while we are not
+    pub error: Option<String>,
+    ///Newest snapshot, older ones are already out of date
+    pub snapshot: Option<Box<Snapshot>>,
+    writer: Arc<Mutex<Option<Connection>>>,
+    receiver: Receiver<Result<AgentMessage, String>>,
+}
+
+impl Remote {
+    fn new(endpoint: Endpoint, writer: Option<Connection>) -> (Self, AgentSender) {
+        let (sender, receiver) = channel();
+        let remote = Self {
+            hostname: endpoint.to_string(),
+            endpoint,
+            actions: false,
+            connected: false,
+            error: None,
+            snapshot: None,
+            writer: Arc::new(Mutex::new(writer)),
+            receiver,
+        };
+        (remote, sender)
+    }
+
+    ///Connect and say hello, failing straight away if the agent is not there or refuses us
+    pub fn connect(endpoint: Endpoint, token: Option<String>) -> Result<Self> {
+        let (connection, reader, welcome) = handshake(&endpoint, token.as_deref())?;
+        let (remote, sender) = Self::new(endpoint.clone(), Some(connection));
+        sender.send(Ok(welcome))?;
+        let writer = remote.writer.clone();
+        std::thread::spawn(move || read_agent(endpoint, token, Some(reader), writer, sender));
+        Ok(remote)
+    }
+
+    ///Keep trying to connect in the background, for when one host being down
+    ///should not stop us watching the others
+    pub fn watch(endpoint: Endpoint, token: Option<String>) -> Self {
+        let (remote, sender) = Self::new(endpoint.clone(), None);
+        let writer = remote.writer.clone();
+        std::thread::spawn(move || read_agent(endpoint, token, None, writer, sender));
+        remote
+    }
+
+    pub fn poll(&mut self) -> RemoteUpdate {
+        let mut update = RemoteUpdate::default();
+        for message in self.receiver.try_iter() {
+            match message {
+                Ok(AgentMessage::Welcome {
+                    hostname, actions, ..
+                }) => {
+                    update
+                        .notices
+                        .push(format!("Connected to {hostname} at {}", self.endpoint));
+                    self.hostname = hostname;
+                    self.actions = actions;
+                    self.connected = true;
+                    self.error = None;
+                }
+                Ok(AgentMessage::Snapshot(snapshot)) => {
+                    self.snapshot = Some(snapshot);
+                    update.fresh = true;
+                }
+                Ok(AgentMessage::ActionDone(message)) => {
+                    update.notices.push(format!("{}: {message}", self.hostname))
+                }
+                Ok(AgentMessage::ActionFailed(message)) => update
+                    .notices
+                    .push(format!("{} failed: {message}", self.hostname)),
+                Ok(AgentMessage::Refused(message)) => update
+                    .notices
+                    .push(format!("{} refused us: {message}", self.hostname)),
+                Err(e) => {
+                    self.connected = false;
+                    update
+                        .notices
+                        .push(format!("{}: {e}, retrying", self.hostname));
+                    self.error = Some(e);
+                }
+            }
+        }
+        update
+    }
+
+    ///Processes of the newest snapshot, each knowing its host, by `label`, and that host's clock
+    pub fn processes(&self, label: &str) -> Vec<ProcessInfo> {
+        let Some(snapshot) = &self.snapshot else {
+            return Vec::new();
+        };
+        let mut processes = snapshot.processes.clone();
+        for process in &mut processes {
+            process.host = Some(label.to_string());
+            process.host_clock = Some(snapshot.clock);
+        }
+        processes
+    }
+
+    ///Ask the agent to do something, the answer comes back through `poll`
+    pub fn send(&self, message: &ClientMessage) -> Result<()> {
+        if !self.actions {
+            bail!(
+                "{} is read only, start its agent with a token to allow this",
+                self.hostname
+            );
+        }
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| anyhow!("Connection lock poisoned"))?;
+        match writer.as_mut() {
+            Some(connection) => write_message(connection, message),
+            None => bail!("Not connected to {}", self.hostname),
+        }
+    }
+
+    ///A host that never connects, with a snapshot already in
+    #[cfg(test)]
+    pub fn offline(hostname: &str, snapshot: Snapshot) -> Self {
+        let (mut remote, _) = Self::new(Endpoint::Tcp(format!("{hostname}:8687")), None);
+        remote.hostname = hostname.to_string();
+        remote.connected = true;
+        remote.snapshot = Some(Box::new(snapshot));
+        remote
+    }
+}
+
+fn handshake(
+    endpoint: &Endpoint,
+    token: Option<&str>,
+) -> Result<(Connection, BufReader<Connection>, AgentMessage)> {
+    let mut connection = endpoint.connect()?;
+    write_message(
+        &mut connection,
+        &ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            token: token.map(String::from),
+        },
+    )?;
+    let mut reader = BufReader::new(connection.try_clone()?);
+    match read_message(&mut reader, MAX_MESSAGE_LEN)? {
+        Some(welcome @ AgentMessage::Welcome { .. }) => Ok((connection, reader, welcome)),
+        Some(AgentMessage::Refused(reason)) => bail!("{endpoint} refused us: {reason}"),
+        Some(_) => bail!("{endpoint} did not say hello"),
+        None => bail!("{endpoint} hung up"),
+    }
+}
+
+///Pass on everything the agent sends, (re)connecting whenever there is no connection,
+///until nobody is listening any more
+fn read_agent(
+    endpoint: Endpoint,
+    token: Option<String>,
+    mut reader: Option<BufReader<Connection>>,
+    writer: Arc<Mutex<Option<Connection>>>,
+    sender: AgentSender,
+) {
+    loop {
+        if let Some(mut current) = reader.take() {
+            let lost = loop {
+                match read_message(&mut current, MAX_MESSAGE_LEN) {
+                    Ok(Some(message)) => {
+                        if sender.send(Ok(message)).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(None) => break "the agent hung up".to_string(),
+                    Err(e) => break e.to_string(),
+                }
+            };
+            if let Ok(mut writer) = writer.lock() {
+                *writer = None;
+            }
+            if sender.send(Err(lost)).is_err() {
+                return;
+            }
+            std::thread::sleep(RECONNECT_DELAY);
+        }
+        //agents restart and networks come back, keep trying but only say so when the reason changes
+        let mut reported: Option<String> = None;
+        while reader.is_none() {
+            match handshake(&endpoint, token.as_deref()) {
+                Ok((connection, new_reader, welcome)) => {
+                    if let Ok(mut writer) = writer.lock() {
+                        *writer = Some(connection);
+                    }
+                    reader = Some(new_reader);
+                    if sender.send(Ok(welcome)).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    let e = format!("{e:#}");
+                    if reported.as_ref() != Some(&e) {
+                        if sender.send(Err(e.clone())).is_err() {
+                            return;
+                        }
+                        reported = Some(e);
+                    }
+                    std::thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        }
+    }
+}
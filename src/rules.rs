@@ -0,0 +1,740 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+
+use crate::{app::RowKey, model::ProcessInfo};
+
+///Comparison used in a predicate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmpOp {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    ///Substring match, text fields only
+    Contains,
+}
+
+impl CmpOp {
+    ///Longest operators first so ">=" is not read as ">"
+    const TOKENS: [(&'static str, CmpOp); 7] = [
+        (">=", CmpOp::Ge),
+        ("<=", CmpOp::Le),
+        ("==", CmpOp::Eq),
+        ("!=", CmpOp::Ne),
+        (">", CmpOp::Gt),
+        ("<", CmpOp::Lt),
+        ("~", CmpOp::Contains),
+    ];
+
+    ///Find the first operator in `s`, returning it with the text either side
+    fn split(s: &str) -> Option<(&str, CmpOp, &str)> {
+        for (i, _) in s.char_indices() {
+            for (token, op) in Self::TOKENS {
+                if s[i..].starts_with(token) {
+                    return Some((&s[..i], op, &s[i + token.len()..]));
+                }
+            }
+        }
+        None
+    }
+
+    fn compare_numbers(&self, a: f64, b: f64) -> bool {
+        match self {
+            CmpOp::Gt => a > b,
+            CmpOp::Ge => a >= b,
+            CmpOp::Lt => a < b,
+            CmpOp::Le => a <= b,
+            CmpOp::Eq => a == b,
+            CmpOp::Ne => a != b,
+            CmpOp::Contains => false,
+        }
+    }
+
+    fn compare_text(&self, a: &str, b: &str) -> bool {
+        match self {
+            CmpOp::Eq => a.eq_ignore_ascii_case(b),
+            CmpOp::Ne => !a.eq_ignore_ascii_case(b),
+            CmpOp::Contains => a.to_lowercase().contains(&b.to_lowercase()),
+            _ => false,
+        }
+    }
+}
+
+///How the value on the right of a predicate is read
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ValueKind {
+    Number,
+    ///Kilobytes, accepting K/M/G/T suffixes
+    Kilobytes,
+    ///Seconds, accepting durations such as "5m"
+    Seconds,
+    ///Seconds since the epoch, given as "14:30" (today), "2025-06-01" or "2025-06-01 14:30"
+    Timestamp,
+    Text,
+}
+
+///Process attribute a predicate can test
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Pid,
+    Ppid,
+    Name,
+    Command,
+    User,
+    State,
+    Wchan,
+    Cpu,
+    Memory,
+    VirtualMemory,
+    Threads,
+    Nice,
+    ///CPU the process last ran on
+    LastCpu,
+    ///Allowed CPU list, e.g. "0-3"
+    Affinity,
+    ///Per second rates of page faults and context switches
+    MinorFaults,
+    MajorFaults,
+    VoluntarySwitches,
+    InvoluntarySwitches,
+    OomScore,
+    OomScoreAdj,
+    ///Effective user id, which differs from the owner for setuid programs
+    EffectiveUid,
+    ///SELinux context or AppArmor profile
+    Label,
+    ///When the process started
+    Start,
+    ///How long ago the process started
+    Age,
+    ///Cumulative cpu time
+    Time,
+}
+
+impl Field {
+    pub fn parse(name: &str) -> Option<Self> {
+        let field = match name.to_lowercase().as_str() {
+            "pid" => Field::Pid,
+            "ppid" => Field::Ppid,
+            "name" => Field::Name,
+            "command" | "cmd" => Field::Command,
+            "user" => Field::User,
+            "state" => Field::State,
+            "wchan" => Field::Wchan,
+            "cpu" | "cpu_percent" => Field::Cpu,
+            "mem" | "memory" | "memory_kb" => Field::Memory,
+            "vmem" | "virtual_memory_kb" => Field::VirtualMemory,
+            "threads" | "num_threads" => Field::Threads,
+            "nice" => Field::Nice,
+            "last_cpu" | "processor" => Field::LastCpu,
+            "affinity" | "cpus_allowed" => Field::Affinity,
+            "minflt" | "minor_faults" => Field::MinorFaults,
+            "majflt" | "major_faults" => Field::MajorFaults,
+            "csw" | "voluntary_switches" => Field::VoluntarySwitches,
+            "icsw" | "involuntary_switches" => Field::InvoluntarySwitches,
+            "euid" => Field::EffectiveUid,
+            "label" | "context" => Field::Label,
+            "oom" | "oom_score" => Field::OomScore,
+            "oom_adj" | "oom_score_adj" => Field::OomScoreAdj,
+            "start" | "started" => Field::Start,
+            "age" => Field::Age,
+            "time" | "cpu_time" => Field::Time,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    fn kind(&self) -> ValueKind {
+        match self {
+            Field::Name
+            | Field::Command
+            | Field::User
+            | Field::State
+            | Field::Wchan
+            | Field::Affinity
+            | Field::Label => ValueKind::Text,
+            Field::Memory | Field::VirtualMemory => ValueKind::Kilobytes,
+            Field::Age | Field::Time => ValueKind::Seconds,
+            Field::Start => ValueKind::Timestamp,
+            _ => ValueKind::Number,
+        }
+    }
+
+    fn number(&self, process: &ProcessInfo) -> f64 {
+        match self {
+            Field::Pid => process.pid as f64,
+            Field::Ppid => process.ppid as f64,
+            Field::Cpu => process.cpu_percent,
+            Field::Memory => process.memory_kb as f64,
+            Field::VirtualMemory => process.virtual_memory_kb as f64,
+            Field::Threads => process.num_threads as f64,
+            Field::Nice => process.nice as f64,
+            Field::LastCpu => process.last_cpu.map(|cpu| cpu as f64).unwrap_or(-1.0),
+            Field::MinorFaults => process.activity_rates.minor_faults,
+            Field::MajorFaults => process.activity_rates.major_faults,
+            Field::VoluntarySwitches => process.activity_rates.voluntary_switches,
+            Field::InvoluntarySwitches => process.activity_rates.involuntary_switches,
+            Field::EffectiveUid => process.security.uids.effective as f64,
+            Field::OomScore => process.oom_score as f64,
+            Field::OomScoreAdj => process.oom_score_adj as f64,
+            Field::Start => process.start_epoch_secs(),
+            Field::Age => process.age_seconds() as f64,
+            Field::Time => process.cpu_time_secs(),
+            _ => 0.0,
+        }
+    }
+
+    fn text(&self, process: &ProcessInfo) -> String {
+        match self {
+            Field::Name => process.name.clone(),
+            Field::Command => process.command.clone(),
+            Field::User => process.user.clone(),
+            Field::State => format!("{:?}", process.state),
+            Field::Wchan => process.wchan.clone(),
+            Field::Affinity => process.cpus_allowed.clone(),
+            Field::Label => process.security.label.clone().unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+}
+
+///A single `field op value` test against a process, e.g. `cpu > 90` or `name == worker`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate {
+    pub field: Field,
+    pub op: CmpOp,
+    pub value: String,
+    number: f64,
+}
+
+impl Predicate {
+    pub fn parse(s: &str) -> Result<Self> {
+        let (field, op, value) =
+            CmpOp::split(s).ok_or_else(|| anyhow!("No comparison in '{s}'"))?;
+        let field = Field::parse(field.trim())
+            .ok_or_else(|| anyhow!("Unknown field '{}'", field.trim()))?;
+        let value = value.trim().trim_matches('"').to_string();
+        let number = match field.kind() {
+            ValueKind::Text => 0.0,
+            _ if op == CmpOp::Contains => {
+                return Err(anyhow!("'~' only works on text fields"));
+            }
+            ValueKind::Number => value
+                .parse::<f64>()
+                .with_context(|| format!("'{value}' is not a number"))?,
+            ValueKind::Kilobytes => parse_kilobytes(&value)?,
+            ValueKind::Seconds => parse_duration(&value)?.as_secs_f64(),
+            ValueKind::Timestamp => parse_timestamp(&value)?,
+        };
+        Ok(Self {
+            field,
+            op,
+            value,
+            number,
+        })
+    }
+
+    pub fn matches(&self, process: &ProcessInfo) -> bool {
+        match self.field.kind() {
+            ValueKind::Text => {
+                let text = self.field.text(process);
+                //states can also be given by their single letter, e.g. "state == D"
+                if self.field == Field::State && self.value.chars().count() == 1 {
+                    return self
+                        .op
+                        .compare_text(&process.state.as_char().to_string(), &self.value);
+                }
+                self.op.compare_text(&text, &self.value)
+            }
+            _ => self
+                .op
+                .compare_numbers(self.field.number(process), self.number),
+        }
+    }
+}
+
+///Sizes in kilobytes, "512" is 512K, "4G" is 4194304K
+fn parse_kilobytes(value: &str) -> Result<f64> {
+    let upper = value.to_uppercase();
+    let trimmed = upper.trim_end_matches('B');
+    let (digits, multiplier) = match trimmed.chars().last() {
+        Some('K') => (&trimmed[..trimmed.len() - 1], 1.0),
+        Some('M') => (&trimmed[..trimmed.len() - 1], 1024.0),
+        Some('G') => (&trimmed[..trimmed.len() - 1], 1024.0 * 1024.0),
+        Some('T') => (&trimmed[..trimmed.len() - 1], 1024.0 * 1024.0 * 1024.0),
+        _ => (trimmed, 1.0),
+    };
+    let number = digits
+        .trim()
+        .parse::<f64>()
+        .with_context(|| format!("'{value}' is not a size"))?;
+    Ok(number * multiplier)
+}
+
+///Local date and/or time as seconds since the epoch, a bare time means today
+fn parse_timestamp(value: &str) -> Result<f64> {
+    let datetime = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| date.and_time(NaiveTime::MIN))
+        })
+        .or_else(|_| {
+            NaiveTime::parse_from_str(value, "%H:%M")
+                .map(|time| Local::now().date_naive().and_time(time))
+        })
+        .with_context(|| format!("'{value}' is not a time, use HH:MM or YYYY-MM-DD [HH:MM]"))?;
+    Local
+        .from_local_datetime(&datetime)
+        .earliest()
+        .map(|t| t.timestamp() as f64)
+        .ok_or_else(|| anyhow!("'{value}' does not exist in the local time zone"))
+}
+
+///Durations like "30s", "5m", "2h", "1d" or plain seconds
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let (digits, multiplier) = match value.chars().last() {
+        Some('s') => (&value[..value.len() - 1], 1),
+        Some('m') => (&value[..value.len() - 1], 60),
+        Some('h') => (&value[..value.len() - 1], 3600),
+        Some('d') => (&value[..value.len() - 1], 86400),
+        _ => (value, 1),
+    };
+    let number = digits
+        .parse::<u64>()
+        .with_context(|| format!("'{value}' is not a duration"))?;
+    Ok(Duration::from_secs(number * multiplier))
+}
+
+///What a rule looks at
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    ///True for each process matching the predicate
+    Process(Predicate),
+    ///True when the number of processes matching the predicate compares to `count`
+    Count {
+        predicate: Predicate,
+        op: CmpOp,
+        count: f64,
+    },
+}
+
+impl Condition {
+    fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let Some(inner) = s.strip_prefix("count(") else {
+            return Ok(Condition::Process(Predicate::parse(s)?));
+        };
+        let close = inner
+            .rfind(')')
+            .ok_or_else(|| anyhow!("Missing ')' in '{s}'"))?;
+        let predicate = Predicate::parse(&inner[..close])?;
+        let (_, op, count) =
+            CmpOp::split(&inner[close + 1..]).ok_or_else(|| anyhow!("No comparison in '{s}'"))?;
+        let count = count
+            .trim()
+            .parse::<f64>()
+            .with_context(|| format!("'{}' is not a count", count.trim()))?;
+        Ok(Condition::Count {
+            predicate,
+            op,
+            count,
+        })
+    }
+}
+
+///What happens when a rule fires
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    ///Color the matching rows while the condition holds
+    Highlight,
+    ///Show a notice in the status bar
+    Toast,
+    ///Run a shell command, `{pid}`, `{name}`, `{count}` and `{rule}` stand for
+    ///`"$RUSTOP_PID"`, `"$RUSTOP_NAME"`, `"$RUSTOP_COUNT"` and `"$RUSTOP_RULE"`.
+    ///It runs here, so never for a process of a remote agent.
+    Exec(String),
+    ///Append a line to a file
+    Log(PathBuf),
+}
+
+impl Action {
+    fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (name, argument) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let argument = argument.trim();
+        match name {
+            "highlight" => Ok(Action::Highlight),
+            "toast" => Ok(Action::Toast),
+            "exec" if !argument.is_empty() => Ok(Action::Exec(argument.to_string())),
+            "log" if !argument.is_empty() => Ok(Action::Log(PathBuf::from(argument))),
+            "exec" | "log" => Err(anyhow!("'{name}' needs an argument")),
+            _ => Err(anyhow!("Unknown action '{name}'")),
+        }
+    }
+}
+
+///`<condition> [for <duration>] => <action>[; <action>...]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub source: String,
+    pub condition: Condition,
+    ///How long the condition has to hold before the rule fires
+    pub sustain: Duration,
+    pub actions: Vec<Action>,
+}
+
+impl Rule {
+    pub fn parse(line: &str) -> Result<Self> {
+        let (condition, actions) = line
+            .split_once("=>")
+            .ok_or_else(|| anyhow!("Missing '=>' between condition and actions"))?;
+        //" for " can be part of a text value too, it is only a sustain if a duration follows
+        let sustain = condition
+            .rsplit_once(" for ")
+            .and_then(|(condition, duration)| Some((condition, parse_duration(duration).ok()?)));
+        let (condition, sustain) = sustain.unwrap_or((condition, Duration::ZERO));
+        let actions = actions
+            .split(';')
+            .filter(|a| !a.trim().is_empty())
+            .map(Action::parse)
+            .collect::<Result<Vec<_>>>()?;
+        if actions.is_empty() {
+            return Err(anyhow!("No actions"));
+        }
+        Ok(Self {
+            source: line.trim().to_string(),
+            condition: Condition::parse(condition)?,
+            sustain,
+            actions,
+        })
+    }
+}
+
+///Identifies one thing a rule can be true for: a process or the whole table
+type Subject = (usize, Option<RowKey>);
+
+///Rules loaded from a file, evaluated after each refresh
+#[derive(Debug, Default)]
+pub struct RuleEngine {
+    pub rules: Vec<Rule>,
+    ///When each subject started matching
+    matching_since: HashMap<Subject, Instant>,
+    ///Subjects we already fired for, until they stop matching
+    fired: HashSet<Subject>,
+    ///Pids to color in the process table
+    pub highlighted: HashSet<RowKey>,
+}
+
+impl RuleEngine {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read rules from {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid rules in {}", path.display()))
+    }
+
+    ///One rule per line, blank lines and lines starting with '#' are ignored
+    pub fn parse(text: &str) -> Result<Self> {
+        let rules = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim().starts_with('#'))
+            .map(|(i, line)| Rule::parse(line).with_context(|| format!("line {}", i + 1)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            rules,
+            ..Self::default()
+        })
+    }
+
+    ///Whether any rule tests `field`
+    pub fn uses(&self, field: Field) -> bool {
+        self.rules.iter().any(|rule| match &rule.condition {
+            Condition::Process(predicate) | Condition::Count { predicate, .. } => {
+                predicate.field == field
+            }
+        })
+    }
+
+    ///Check every rule against the current processes, running actions for rules that fire.
+    ///Returns the messages to show in the status bar.
+    pub fn evaluate(&mut self, processes: &[ProcessInfo]) -> Vec<String> {
+        let now = Instant::now();
+        let mut matching: HashSet<Subject> = HashSet::new();
+        let mut toasts = Vec::new();
+        self.highlighted.clear();
+
+        for (idx, rule) in self.rules.iter().enumerate() {
+            //each subject that currently matches, and the process it concerns if any
+            let subjects: Vec<(Subject, Option<&ProcessInfo>)> = match &rule.condition {
+                Condition::Process(predicate) => processes
+                    .iter()
+                    .filter(|p| predicate.matches(p))
+                    .map(|p| ((idx, Some(RowKey::of(p))), Some(p)))
+                    .collect(),
+                Condition::Count {
+                    predicate,
+                    op,
+                    count,
+                } => {
+                    let n = processes.iter().filter(|p| predicate.matches(p)).count();
+                    if op.compare_numbers(n as f64, *count) {
+                        vec![((idx, None), None)]
+                    } else {
+                        vec![]
+                    }
+                }
+            };
+
+            for (subject, process) in subjects {
+                matching.insert(subject.clone());
+                let since = *self.matching_since.entry(subject.clone()).or_insert(now);
+                if now.duration_since(since) < rule.sustain {
+                    continue;
+                }
+                if let Some(process) = process
+                    && rule.actions.contains(&Action::Highlight)
+                {
+                    self.highlighted.insert(RowKey::of(process));
+                }
+                if !self.fired.insert(subject) {
+                    continue;
+                }
+                let count = match &rule.condition {
+                    Condition::Count { predicate, .. } => {
+                        processes.iter().filter(|p| predicate.matches(p)).count()
+                    }
+                    Condition::Process(_) => 1,
+                };
+                let message = match process {
+                    Some(p) => match &p.host {
+                        Some(host) => format!("{} ({} {} on {host})", rule.source, p.pid, p.name),
+                        None => format!("{} ({} {})", rule.source, p.pid, p.name),
+                    },
+                    None => format!("{} ({count} processes)", rule.source),
+                };
+                for action in &rule.actions {
+                    match action {
+                        Action::Highlight => {}
+                        Action::Toast => toasts.push(message.clone()),
+                        //commands run on this host, where the pid is some other process
+                        Action::Exec(_) if process.is_some_and(|p| p.host.is_some()) => {
+                            toasts.push(format!("Not running exec for another host: {message}"))
+                        }
+                        Action::Exec(command) => run_command(command, rule, process, count),
+                        Action::Log(path) => append_log(path, &message),
+                    }
+                }
+            }
+        }
+
+        //anything that stopped matching has to hold for the full duration again
+        self.matching_since
+            .retain(|subject, _| matching.contains(subject));
+        self.fired.retain(|subject| matching.contains(subject));
+        toasts
+    }
+}
+
+///The shell command of an exec action. Names and rules are never pasted into the script,
+///a process can name itself `x;rm -rf ~`, the shell reads them from the environment instead.
+fn shell_command(
+    command: &str,
+    rule: &Rule,
+    process: Option<&ProcessInfo>,
+    count: usize,
+) -> Command {
+    let script = command
+        .replace("{pid}", "\"$RUSTOP_PID\"")
+        .replace("{name}", "\"$RUSTOP_NAME\"")
+        .replace("{count}", "\"$RUSTOP_COUNT\"")
+        .replace("{rule}", "\"$RUSTOP_RULE\"");
+    let mut shell = Command::new("sh");
+    shell
+        .arg("-c")
+        .arg(script)
+        .env(
+            "RUSTOP_PID",
+            process.map(|p| p.pid.to_string()).unwrap_or_default(),
+        )
+        .env(
+            "RUSTOP_NAME",
+            process.map(|p| p.name.as_str()).unwrap_or(""),
+        )
+        .env("RUSTOP_COUNT", count.to_string())
+        .env("RUSTOP_RULE", &rule.source)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    shell
+}
+
+///Fire and forget a shell command, reaping it on a separate thread
+fn run_command(command: &str, rule: &Rule, process: Option<&ProcessInfo>, count: usize) {
+    let child = shell_command(command, rule, process, count).spawn();
+    if let Ok(mut child) = child {
+        std::thread::spawn(move || child.wait());
+    }
+}
+
+fn append_log(path: &Path, message: &str) {
+    let file = OpenOptions::new().create(true).append(true).open(path);
+    if let Ok(mut file) = file {
+        let _ = writeln!(
+            file,
+            "{} {}",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            message
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::ProcessState;
+    use crate::processes::{get_boot_time, get_clock_ticks};
+
+    fn process(pid: u64, name: &str, cpu_percent: f64, memory_kb: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.to_string(),
+            cpu_percent,
+            memory_kb,
+            state: ProcessState::Sleeping,
+            ..ProcessInfo::new()
+        }
+    }
+
+    #[test]
+    pub fn test_parse_rules() -> Result<()> {
+        let engine = RuleEngine::parse(
+            "# comment\n\
+             cpu_percent > 90 for 30s => highlight; toast\n\
+             memory_kb > 4G => exec logger {pid}\n\
+             count(name == worker) < 4 => log /tmp/rustop.log\n",
+        )?;
+        assert_eq!(engine.rules.len(), 3);
+        assert_eq!(engine.rules[0].sustain, Duration::from_secs(30));
+        assert_eq!(
+            engine.rules[0].actions,
+            vec![Action::Highlight, Action::Toast]
+        );
+        let Condition::Process(predicate) = &engine.rules[1].condition else {
+            panic!("expected a process condition");
+        };
+        assert_eq!(predicate.number, 4.0 * 1024.0 * 1024.0);
+        assert!(matches!(
+            engine.rules[2].condition,
+            Condition::Count { op: CmpOp::Lt, .. }
+        ));
+        assert!(RuleEngine::parse("cpu > 90").is_err());
+        assert!(RuleEngine::parse("bogus > 90 => toast").is_err());
+
+        //"for" inside a value is not a sustain
+        let rule = Rule::parse("name ~ \"wait for it\" => toast")?;
+        assert_eq!(rule.sustain, Duration::ZERO);
+        let Condition::Process(predicate) = &rule.condition else {
+            panic!("expected a process condition");
+        };
+        assert_eq!(predicate.value, "wait for it");
+        let rule = Rule::parse("name ~ \"wait for it\" for 5m => toast")?;
+        assert_eq!(rule.sustain, Duration::from_secs(300));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_exec_does_not_run_names() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let rule = Rule::parse("cpu > 90 => exec echo {rule}")?;
+        let hostile = process(7, "x;touch pwned;$(touch pwned)", 95.0, 0);
+        let output = shell_command("echo {pid} {name} {rule}", &rule, Some(&hostile), 1)
+            .current_dir(dir.path())
+            .stdout(Stdio::piped())
+            .output()?;
+        assert_eq!(
+            String::from_utf8(output.stdout)?,
+            "7 x;touch pwned;$(touch pwned) cpu > 90 => exec echo {rule}\n"
+        );
+        //neither the name ran nor the rule's '>' redirected into a file
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_evaluate_fires_once_per_episode() -> Result<()> {
+        let mut engine =
+            RuleEngine::parse("cpu > 90 => highlight; toast\nstate == S => highlight")?;
+        let busy = vec![process(1, "spin", 99.0, 0)];
+        assert_eq!(engine.evaluate(&busy).len(), 1);
+        assert!(engine.highlighted.contains(&RowKey::of(&busy[0])));
+        //still true, so no new toast but still highlighted
+        assert!(engine.evaluate(&busy).is_empty());
+        assert!(engine.highlighted.contains(&RowKey::of(&busy[0])));
+        engine.evaluate(&[process(1, "spin", 1.0, 0)]);
+        assert_eq!(engine.evaluate(&busy).len(), 1);
+
+        //the same pid on another host is another process
+        let on = |host: &str, cpu| ProcessInfo {
+            host: Some(host.to_string()),
+            ..process(1, "spin", cpu, 0)
+        };
+        let merged = [on("build1", 99.0), on("build2", 1.0)];
+        let mut engine = RuleEngine::parse("cpu > 90 => highlight")?;
+        engine.evaluate(&merged);
+        assert!(engine.highlighted.contains(&RowKey::of(&merged[0])));
+        assert!(!engine.highlighted.contains(&RowKey::of(&merged[1])));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_exec_stays_on_this_host() -> Result<()> {
+        let mut engine = RuleEngine::parse("cpu > 90 => exec kill -STOP {pid}; log /dev/null")?;
+        let remote = ProcessInfo {
+            host: Some("build1".to_string()),
+            ..process(1, "spin", 99.0, 0)
+        };
+        assert_eq!(
+            engine.evaluate(&[remote]),
+            ["Not running exec for another host: \
+              cpu > 90 => exec kill -STOP {pid}; log /dev/null (1 spin on build1)"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_count_condition() -> Result<()> {
+        let mut engine = RuleEngine::parse("count(name == worker) < 2 => toast")?;
+        let workers = vec![process(1, "worker", 0.0, 0), process(2, "worker", 0.0, 0)];
+        assert!(engine.evaluate(&workers).is_empty());
+        assert_eq!(engine.evaluate(&workers[..1]).len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_time_predicates() -> Result<()> {
+        let ticks = get_clock_ticks();
+        let since_boot = Local::now().timestamp() as u64 - get_boot_time();
+        let young = ProcessInfo {
+            start_time: (since_boot.saturating_sub(10) as f64 * ticks) as u64,
+            cpu_time_total: (90.0 * ticks) as u64,
+            ..process(1, "fresh", 0.0, 0)
+        };
+        assert!(Predicate::parse("age < 5m")?.matches(&young));
+        assert!(!Predicate::parse("age > 1h")?.matches(&young));
+        assert!(Predicate::parse("start > 2020-01-01 09:30")?.matches(&young));
+        assert!(Predicate::parse("time > 1m")?.matches(&young));
+        assert!(Predicate::parse("start > yesterday").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+///Capability names by bit number, as in linux/capability.h
+const CAPABILITY_NAMES: [&str; 41] = [
+    "cap_chown",
+    "cap_dac_override",
+    "cap_dac_read_search",
+    "cap_fowner",
+    "cap_fsetid",
+    "cap_kill",
+    "cap_setgid",
+    "cap_setuid",
+    "cap_setpcap",
+    "cap_linux_immutable",
+    "cap_net_bind_service",
+    "cap_net_broadcast",
+    "cap_net_admin",
+    "cap_net_raw",
+    "cap_ipc_lock",
+    "cap_ipc_owner",
+    "cap_sys_module",
+    "cap_sys_rawio",
+    "cap_sys_chroot",
+    "cap_sys_ptrace",
+    "cap_sys_pacct",
+    "cap_sys_admin",
+    "cap_sys_boot",
+    "cap_sys_nice",
+    "cap_sys_resource",
+    "cap_sys_time",
+    "cap_sys_tty_config",
+    "cap_mknod",
+    "cap_lease",
+    "cap_audit_write",
+    "cap_audit_control",
+    "cap_setfcap",
+    "cap_mac_override",
+    "cap_mac_admin",
+    "cap_syslog",
+    "cap_wake_alarm",
+    "cap_block_suspend",
+    "cap_audit_read",
+    "cap_perfmon",
+    "cap_bpf",
+    "cap_checkpoint_restore",
+];
+
+///Names of the capabilities set in a mask, "cap_<bit>" for ones newer than we know
+pub fn decode_capabilities(mask: u64) -> Vec<String> {
+    (0..64)
+        .filter(|bit| mask & (1 << bit) != 0)
+        .map(|bit| match CAPABILITY_NAMES.get(bit) {
+            Some(name) => name.to_string(),
+            None => format!("cap_{bit}"),
+        })
+        .collect()
+}
+
+///Short form of a capability mask: "none", "all" or the names
+pub fn describe_capabilities(mask: u64) -> String {
+    let names = decode_capabilities(mask);
+    if names.is_empty() {
+        "none".to_string()
+    } else if names.len() >= CAPABILITY_NAMES.len() {
+        "all".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+///The five capability sets of a process
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub inheritable: u64,
+    pub permitted: u64,
+    pub effective: u64,
+    pub bounding: u64,
+    pub ambient: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum SeccompMode {
+    #[default]
+    Disabled,
+    Strict,
+    Filter,
+}
+
+impl SeccompMode {
+    pub fn description(&self) -> &'static str {
+        match self {
+            SeccompMode::Disabled => "disabled",
+            SeccompMode::Strict => "strict",
+            SeccompMode::Filter => "filter",
+        }
+    }
+}
+
+///Real, effective, saved and filesystem ids, in the order `/proc/<pid>/status` lists them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ids {
+    pub real: u32,
+    pub effective: u32,
+    pub saved: u32,
+    pub fs: u32,
+}
+
+impl Ids {
+    fn parse(value: &str) -> Result<Self> {
+        let ids = value
+            .split_whitespace()
+            .map(|id| id.parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()?;
+        match ids.as_slice() {
+            [real, effective, saved, fs] => Ok(Self {
+                real: *real,
+                effective: *effective,
+                saved: *saved,
+                fs: *fs,
+            }),
+            _ => Err(anyhow!("Expected 4 ids, got '{value}'")),
+        }
+    }
+
+    pub fn all(&self) -> [u32; 4] {
+        [self.real, self.effective, self.saved, self.fs]
+    }
+}
+
+///Who a process really runs as and what it is allowed to do
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SecurityContext {
+    pub uids: Ids,
+    pub gids: Ids,
+    pub capabilities: Capabilities,
+    pub seccomp: SeccompMode,
+    pub no_new_privs: bool,
+    ///SELinux context or AppArmor profile, `None` without an LSM that labels processes
+    pub label: Option<String>,
+}
+
+impl SecurityContext {
+    ///Short list of what is unusual about the process, for the security column
+    pub fn flags(&self) -> String {
+        let mut flags = Vec::new();
+        //setuid programs and privilege drops
+        if self.uids.effective != self.uids.real {
+            flags.push(format!("euid={}", self.uids.effective));
+        }
+        if self.gids.effective != self.gids.real {
+            flags.push(format!("egid={}", self.gids.effective));
+        }
+        //all capabilities is what root has anyway
+        let effective = self.capabilities.effective;
+        if effective != 0 && !(self.uids.effective == 0 && effective == self.capabilities.bounding)
+        {
+            flags.push(format!("caps={}", effective.count_ones()));
+        }
+        if self.seccomp != SeccompMode::Disabled {
+            flags.push(format!("seccomp={}", self.seccomp.description()));
+        }
+        if self.no_new_privs {
+            flags.push("nnp".to_string());
+        }
+        if let Some(label) = self.label.as_ref().filter(|l| l.as_str() != "unconfined") {
+            flags.push(label.clone());
+        }
+        flags.join(" ")
+    }
+}
+
+///Parse the credential, capability and seccomp lines of `/proc/<pid>/status`
+pub fn parse_security(status: &str) -> Result<SecurityContext> {
+    let mut context = SecurityContext::default();
+    for line in status.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        let mask = || u64::from_str_radix(value, 16);
+        match key {
+            "Uid" => context.uids = Ids::parse(value)?,
+            "Gid" => context.gids = Ids::parse(value)?,
+            "CapInh" => context.capabilities.inheritable = mask()?,
+            "CapPrm" => context.capabilities.permitted = mask()?,
+            "CapEff" => context.capabilities.effective = mask()?,
+            "CapBnd" => context.capabilities.bounding = mask()?,
+            "CapAmb" => context.capabilities.ambient = mask()?,
+            "NoNewPrivs" => context.no_new_privs = value == "1",
+            "Seccomp" => {
+                context.seccomp = match value {
+                    "1" => SeccompMode::Strict,
+                    "2" => SeccompMode::Filter,
+                    _ => SeccompMode::Disabled,
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(context)
+}
+
+///SELinux or AppArmor label of a process. With stacked LSMs the generic file may be
+///missing, so AppArmor's own is tried too.
+pub fn read_lsm_label(pid: u64) -> Option<String> {
+    ["attr/current", "attr/apparmor/current"]
+        .iter()
+        .filter_map(|file| std::fs::read_to_string(format!("/proc/{pid}/{file}")).ok())
+        .map(|label| {
+            label
+                .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+                .to_string()
+        })
+        .find(|label| !label.is_empty())
+}
+
+///Security context of a process from its status file and LSM attributes
+pub fn read_security(pid: u64) -> Result<SecurityContext> {
+    let mut context = parse_security(&std::fs::read_to_string(format!("/proc/{pid}/status"))?)?;
+    context.label = read_lsm_label(pid);
+    Ok(context)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const STATUS: &str = "\
+Name:\tping
+Uid:\t1000\t0\t0\t0
+Gid:\t1000\t1000\t1000\t1000
+CapInh:\t0000000000000000
+CapPrm:\t0000000000003000
+CapEff:\t0000000000002000
+CapBnd:\t000001ffffffffff
+CapAmb:\t0000000000000000
+NoNewPrivs:\t1
+Seccomp:\t2
+";
+
+    #[test]
+    pub fn test_parse_security() -> Result<()> {
+        let context = parse_security(STATUS)?;
+        assert_eq!(context.uids.real, 1000);
+        assert_eq!(context.uids.effective, 0);
+        assert_eq!(context.gids.all(), [1000; 4]);
+        assert_eq!(
+            decode_capabilities(context.capabilities.permitted),
+            vec!["cap_net_admin", "cap_net_raw"]
+        );
+        assert_eq!(describe_capabilities(context.capabilities.bounding), "all");
+        assert_eq!(describe_capabilities(0), "none");
+        assert_eq!(context.seccomp, SeccompMode::Filter);
+        assert_eq!(context.flags(), "euid=0 caps=1 seccomp=filter nnp");
+        assert!(parse_security("Uid:\t1 2\n").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SensorKind {
+    ///Degrees Celsius
+    Temperature,
+    ///RPM
+    Fan,
+    ///Volts
+    Voltage,
+}
+
+impl SensorKind {
+    pub fn unit(&self) -> &'static str {
+        match self {
+            SensorKind::Temperature => "°C",
+            SensorKind::Fan => "RPM",
+            SensorKind::Voltage => "V",
+        }
+    }
+}
+
+///A single reading from hwmon or a thermal zone
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sensor {
+    ///hwmon chip name or thermal zone type
+    pub chip: String,
+    pub label: String,
+    pub kind: SensorKind,
+    pub value: f64,
+    ///Warning threshold (max for temperatures and voltages)
+    pub high: Option<f64>,
+    pub critical: Option<f64>,
+    ///Minimum for fans and voltages, below it something is wrong
+    pub low: Option<f64>,
+}
+
+///How close a sensor is to its limits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SensorStatus {
+    Normal,
+    High,
+    Critical,
+}
+
+impl Sensor {
+    pub fn status(&self) -> SensorStatus {
+        if self.critical.is_some_and(|crit| self.value >= crit) {
+            SensorStatus::Critical
+        } else if self.high.is_some_and(|high| self.value >= high)
+            || self.low.is_some_and(|low| self.value < low)
+        {
+            SensorStatus::High
+        } else {
+            SensorStatus::Normal
+        }
+    }
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+fn read_number(path: &Path) -> Option<f64> {
+    read_trimmed(path)?.parse::<f64>().ok()
+}
+
+///Entries of a directory matching `prefix`, sorted so readings come out in a stable order
+fn entries_with_prefix(dir: &Path, prefix: &str) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_name().to_string_lossy().starts_with(prefix))
+                .map(|e| e.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    entries.sort();
+    entries
+}
+
+///Every temperature, fan and voltage under `<sys_root>/class/hwmon` and
+///`<sys_root>/class/thermal`. `sys_root` is "/sys" outside of tests.
+pub fn read_sensors(sys_root: &Path) -> Vec<Sensor> {
+    let mut sensors = Vec::new();
+    for chip_dir in entries_with_prefix(&sys_root.join("class/hwmon"), "hwmon") {
+        sensors.extend(read_hwmon_chip(&chip_dir));
+    }
+    for zone_dir in entries_with_prefix(&sys_root.join("class/thermal"), "thermal_zone") {
+        sensors.extend(read_thermal_zone(&zone_dir));
+    }
+    sensors
+}
+
+fn read_hwmon_chip(chip_dir: &Path) -> Vec<Sensor> {
+    let chip = read_trimmed(&chip_dir.join("name")).unwrap_or_else(|| {
+        chip_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    let mut sensors = Vec::new();
+    for input in entries_with_prefix(chip_dir, "") {
+        let file_name = input
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        //temp1_input, fan2_input, in0_input
+        let Some(channel) = file_name.strip_suffix("_input") else {
+            continue;
+        };
+        let (kind, scale) = if channel.starts_with("temp") {
+            (SensorKind::Temperature, 1000.0)
+        } else if channel.starts_with("fan") {
+            (SensorKind::Fan, 1.0)
+        } else if channel
+            .strip_prefix("in")
+            .is_some_and(|n| n.chars().all(|c| c.is_ascii_digit()))
+        {
+            (SensorKind::Voltage, 1000.0)
+        } else {
+            continue;
+        };
+        let Some(value) = read_number(&input) else {
+            continue;
+        };
+        let attribute = |name: &str| {
+            read_number(&chip_dir.join(format!("{channel}_{name}"))).map(|v| v / scale)
+        };
+        sensors.push(Sensor {
+            chip: chip.clone(),
+            label: read_trimmed(&chip_dir.join(format!("{channel}_label")))
+                .unwrap_or_else(|| channel.to_string()),
+            kind,
+            value: value / scale,
+            high: attribute("max"),
+            critical: attribute("crit"),
+            low: attribute("min"),
+        });
+    }
+    sensors
+}
+
+fn read_thermal_zone(zone_dir: &Path) -> Option<Sensor> {
+    let value = read_number(&zone_dir.join("temp"))? / 1000.0;
+    let mut high = None;
+    let mut critical = None;
+    for trip_type in entries_with_prefix(zone_dir, "trip_point_") {
+        let file_name = trip_type
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let Some(trip) = file_name.strip_suffix("_type") else {
+            continue;
+        };
+        let temp = read_number(&zone_dir.join(format!("{trip}_temp"))).map(|t| t / 1000.0);
+        match read_trimmed(&trip_type).as_deref() {
+            Some("critical") => critical = temp,
+            Some("hot") => high = temp,
+            _ => {}
+        }
+    }
+    Some(Sensor {
+        chip: "thermal".to_string(),
+        label: read_trimmed(&zone_dir.join("type")).unwrap_or_else(|| {
+            zone_dir
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default()
+        }),
+        kind: SensorKind::Temperature,
+        value,
+        high,
+        critical,
+        low: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::Result;
+
+    fn write(root: &Path, path: &str, content: &str) -> Result<()> {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap_or(root))?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_read_sensors_from_fake_sysfs() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let sys = root.path();
+        write(sys, "class/hwmon/hwmon0/name", "coretemp\n")?;
+        write(sys, "class/hwmon/hwmon0/temp1_input", "95000\n")?;
+        write(sys, "class/hwmon/hwmon0/temp1_label", "Package id 0\n")?;
+        write(sys, "class/hwmon/hwmon0/temp1_max", "80000\n")?;
+        write(sys, "class/hwmon/hwmon0/temp1_crit", "100000\n")?;
+        write(sys, "class/hwmon/hwmon1/name", "nct6775\n")?;
+        write(sys, "class/hwmon/hwmon1/fan1_input", "300\n")?;
+        write(sys, "class/hwmon/hwmon1/fan1_min", "500\n")?;
+        write(sys, "class/hwmon/hwmon1/in0_input", "1200\n")?;
+        write(sys, "class/thermal/thermal_zone0/type", "acpitz\n")?;
+        write(sys, "class/thermal/thermal_zone0/temp", "105000\n")?;
+        write(
+            sys,
+            "class/thermal/thermal_zone0/trip_point_0_type",
+            "critical\n",
+        )?;
+        write(
+            sys,
+            "class/thermal/thermal_zone0/trip_point_0_temp",
+            "100000\n",
+        )?;
+
+        let sensors = read_sensors(sys);
+        assert_eq!(sensors.len(), 4);
+
+        let package = &sensors[0];
+        assert_eq!(package.chip, "coretemp");
+        assert_eq!(package.label, "Package id 0");
+        assert_eq!(package.value, 95.0);
+        assert_eq!(package.status(), SensorStatus::High);
+
+        let fan = sensors.iter().find(|s| s.kind == SensorKind::Fan).unwrap();
+        assert_eq!(fan.label, "fan1");
+        assert_eq!(fan.status(), SensorStatus::High);
+
+        let voltage = sensors
+            .iter()
+            .find(|s| s.kind == SensorKind::Voltage)
+            .unwrap();
+        assert_eq!(voltage.value, 1.2);
+        assert_eq!(voltage.status(), SensorStatus::Normal);
+
+        let zone = &sensors[3];
+        assert_eq!(zone.label, "acpitz");
+        assert_eq!(zone.status(), SensorStatus::Critical);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_missing_sysfs_is_empty() {
+        assert!(read_sensors(Path::new("/nonexistent")).is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+use std::{io::Write, sync::Arc, time::Duration};
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    collector::Collector,
+    detail::ProcessDetail,
+    kstack::ThreadStack,
+    limits::{Limit, LimitUsage},
+    memmap::MappingSummary,
+    model::ProcessInfo,
+    security::SecurityContext,
+};
+
+///The dashboard, bundled so the binary is all there is to deploy
+const INDEX_HTML: &str = include_str!("web/index.html");
+
+///What the detail view shows, for `/api/processes/<pid>`
+#[derive(Serialize)]
+struct DetailResponse<'a> {
+    process: &'a ProcessInfo,
+    mappings: &'a [MappingSummary],
+    threads: &'a [ThreadStack],
+    limits: Option<&'a Vec<Limit>>,
+    limit_usage: &'a LimitUsage,
+    security: Option<&'a SecurityContext>,
+    ///Whatever we could not read, usually for lack of privileges
+    errors: Vec<String>,
+}
+
+///How to answer a request
+#[derive(Debug, PartialEq)]
+enum Reply {
+    Html(&'static str),
+    Json(Arc<String>),
+    ///Server-sent events, a snapshot every refresh
+    Stream,
+    Error(u16, String),
+}
+
+fn route(method: &Method, url: &str, collector: &Collector) -> Result<Reply> {
+    if *method != Method::Get {
+        return Ok(Reply::Error(405, "Only GET is supported".to_string()));
+    }
+    let path = url.split('?').next().unwrap_or(url);
+    let Some((snapshot, json)) = collector.latest() else {
+        return Ok(Reply::Error(503, "No snapshot taken yet".to_string()));
+    };
+    let reply = match path {
+        "/" | "/index.html" => Reply::Html(INDEX_HTML),
+        "/api/snapshot" => Reply::Json(json),
+        "/api/system" => Reply::Json(Arc::new(serde_json::to_string(&snapshot.system)?)),
+        "/api/stream" => Reply::Stream,
+        _ => match path.strip_prefix("/api/processes/") {
+            Some(pid) => match pid.parse::<u64>() {
+                Ok(pid) => match snapshot.processes.iter().find(|p| p.pid == pid) {
+                    Some(process) => Reply::Json(Arc::new(detail_json(process)?)),
+                    None => Reply::Error(404, format!("No process {pid}")),
+                },
+                Err(_) => Reply::Error(400, format!("'{pid}' is not a pid")),
+            },
+            None => Reply::Error(404, format!("Nothing at {path}")),
+        },
+    };
+    Ok(reply)
+}
+
+fn detail_json(process: &ProcessInfo) -> Result<String> {
+    let detail = ProcessDetail::load(process);
+    let errors = [
+        detail.regions.as_ref().err(),
+        detail.limits.as_ref().err(),
+        detail.security.as_ref().err(),
+    ]
+    .into_iter()
+    .flatten()
+    .cloned()
+    .collect();
+    Ok(serde_json::to_string(&DetailResponse {
+        process,
+        mappings: &detail.mappings,
+        threads: &detail.threads,
+        limits: detail.limits.as_ref().ok(),
+        limit_usage: &detail.limit_usage,
+        security: detail.security.as_ref().ok(),
+        errors,
+    })?)
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("static header is valid")
+}
+
+fn respond(request: Request, collector: &Arc<Collector>) -> Result<()> {
+    let reply = route(request.method(), request.url(), collector)
+        .unwrap_or_else(|e| Reply::Error(500, e.to_string()));
+    match reply {
+        Reply::Html(html) => request.respond(
+            Response::from_string(html)
+                .with_header(header("Content-Type", "text/html; charset=utf-8")),
+        )?,
+        Reply::Json(json) => request.respond(
+            Response::from_string(json.as_str())
+                .with_header(header("Content-Type", "application/json")),
+        )?,
+        Reply::Error(status, message) => {
+            request.respond(Response::from_string(message).with_status_code(status))?
+        }
+        Reply::Stream => {
+            let collector = collector.clone();
+            //one thread per stream, the server keeps answering everyone else
+            std::thread::spawn(move || stream(request, &collector));
+        }
+    }
+    Ok(())
+}
+
+///Send every snapshot as a server-sent event until the client goes away
+fn stream(request: Request, collector: &Collector) -> Result<()> {
+    let receiver = collector.subscribe()?;
+    //written by hand, tiny_http would hold events back until its chunk buffer fills
+    let mut writer = request.into_writer();
+    writer.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+    )?;
+    for json in receiver {
+        writer.write_all(b"data: ")?;
+        writer.write_all(json.as_bytes())?;
+        writer.write_all(b"\n\n")?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+///Serve the JSON API and the dashboard on `listen` until killed
+pub fn serve(listen: &str, interval: Duration) -> Result<()> {
+    let server = Server::http(listen).map_err(|e| anyhow!("Cannot listen on {listen}: {e}"))?;
+    let collector = Collector::start(interval);
+    println!("Dashboard on http://{listen}/, JSON under /api");
+    for request in server.incoming_requests() {
+        if let Err(e) = respond(request, &collector) {
+            eprintln!("Request failed: {e}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        model::HostClock,
+        system::{Snapshot, SystemStats},
+    };
+
+    #[test]
+    pub fn test_route() -> Result<()> {
+        let collector = Collector::default();
+        assert!(matches!(
+            route(&Method::Get, "/", &collector)?,
+            Reply::Error(503, _)
+        ));
+
+        let mut process = ProcessInfo::new();
+        process.pid = std::process::id() as u64;
+        collector.publish(Snapshot {
+            timestamp: 0,
+            clock: HostClock {
+                boot_time: 0,
+                clock_ticks: 100.0,
+            },
+            system: SystemStats::default(),
+            processes: vec![process],
+        })?;
+        assert_eq!(
+            route(&Method::Get, "/", &collector)?,
+            Reply::Html(INDEX_HTML)
+        );
+        assert_eq!(
+            route(&Method::Get, "/api/stream", &collector)?,
+            Reply::Stream
+        );
+        let Reply::Json(json) = route(&Method::Get, "/api/snapshot?x=1", &collector)? else {
+            panic!("snapshot is json");
+        };
+        assert!(json.contains(r#""clock_ticks":100.0"#));
+        assert!(json.contains(r#""state":"?""#));
+        let path = format!("/api/processes/{}", std::process::id());
+        assert!(matches!(
+            route(&Method::Get, &path, &collector)?,
+            Reply::Json(_)
+        ));
+        assert!(matches!(
+            route(&Method::Get, "/api/processes/0", &collector)?,
+            Reply::Error(404, _)
+        ));
+        assert!(matches!(
+            route(&Method::Post, "/api/snapshot", &collector)?,
+            Reply::Error(405, _)
+        ));
+        Ok(())
+    }
+}
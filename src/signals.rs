@@ -0,0 +1,54 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Result, anyhow};
+use nix::{
+    sys::signal::{Signal, kill},
+    unistd::Pid,
+};
+
+///Signals offered in the command palette, the ones worth sending by hand
+pub const COMMON_SIGNALS: [Signal; 7] = [
+    Signal::SIGTERM,
+    Signal::SIGKILL,
+    Signal::SIGINT,
+    Signal::SIGHUP,
+    Signal::SIGSTOP,
+    Signal::SIGCONT,
+    Signal::SIGUSR1,
+];
+
+///Parse a signal the way kill(1) takes it: "TERM", "sigterm", "SIGTERM" or "15"
+pub fn parse_signal(input: &str) -> Result<Signal> {
+    let input = input.trim();
+    if let Ok(number) = input.parse::<i32>() {
+        return Signal::try_from(number).map_err(|_| anyhow!("{number} is not a signal"));
+    }
+    let name = input.to_uppercase();
+    let name = match name.starts_with("SIG") {
+        true => name,
+        false => format!("SIG{name}"),
+    };
+    Signal::from_str(&name).map_err(|_| anyhow!("'{input}' is not a signal"))
+}
+
+pub fn send_signal(pid: u64, signal: Signal) -> Result<()> {
+    kill(Pid::from_raw(pid as i32), signal)
+        .with_context(|| format!("Cannot send {} to {pid}", signal.as_str()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_signal() -> Result<()> {
+        assert_eq!(parse_signal("term")?, Signal::SIGTERM);
+        assert_eq!(parse_signal(" SIGKILL ")?, Signal::SIGKILL);
+        assert_eq!(parse_signal("1")?, Signal::SIGHUP);
+        assert!(parse_signal("nap").is_err());
+        assert!(parse_signal("99").is_err());
+        //continuing ourselves is harmless
+        send_signal(std::process::id() as u64, Signal::SIGCONT)?;
+        Ok(())
+    }
+}
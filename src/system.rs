@@ -0,0 +1,153 @@
+use anyhow::{Context, Result, anyhow};
+use nix::unistd::{SysconfVar, sysconf};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    model::{HostClock, ProcessInfo, ProcessState},
+    pressure::Pressure,
+    processes::get_uptime,
+};
+
+///Everything a client needs to draw the process table, taken on every refresh
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    ///Seconds since the epoch
+    pub timestamp: i64,
+    ///To turn `start_time` and `cpu_time_total`, in clock ticks, into dates and seconds
+    #[serde(flatten)]
+    pub clock: HostClock,
+    pub system: SystemStats,
+    pub processes: Vec<ProcessInfo>,
+}
+
+impl Snapshot {
+    pub fn take(processes: &[ProcessInfo], pressure: Option<[Pressure; 3]>) -> Result<Self> {
+        Ok(Self {
+            timestamp: chrono::Local::now().timestamp(),
+            clock: HostClock::local(),
+            system: SystemStats::read(processes, pressure)?,
+            processes: processes.to_vec(),
+        })
+    }
+}
+
+///Machine wide numbers, what a remote view shows above its process table
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SystemStats {
+    pub hostname: String,
+    pub uptime_secs: f64,
+    ///1, 5 and 15 minute load averages
+    pub load_average: [f64; 3],
+    pub cpu_count: usize,
+    ///Cpu used by all processes, as a share of every cpu
+    pub cpu_percent: f64,
+    pub memory_total_kb: u64,
+    pub memory_available_kb: u64,
+    pub swap_total_kb: u64,
+    pub swap_free_kb: u64,
+    pub tasks: usize,
+    pub running: usize,
+    pub disk_sleep: usize,
+    pub zombies: usize,
+    ///cpu, memory and io pressure, when the kernel has PSI
+    pub pressure: Option<[Pressure; 3]>,
+}
+
+impl SystemStats {
+    ///Read what /proc knows about the machine, with task and cpu totals from `processes`
+    pub fn read(processes: &[ProcessInfo], pressure: Option<[Pressure; 3]>) -> Result<Self> {
+        let mut stats = parse_meminfo(&std::fs::read_to_string("/proc/meminfo")?)?;
+        stats.load_average = parse_loadavg(&std::fs::read_to_string("/proc/loadavg")?)?;
+        stats.hostname = hostname();
+        stats.uptime_secs = get_uptime()?;
+        stats.cpu_count = sysconf(SysconfVar::_NPROCESSORS_ONLN)
+            .ok()
+            .flatten()
+            .unwrap_or(1)
+            .max(1) as usize;
+        stats.cpu_percent =
+            processes.iter().map(|p| p.cpu_percent).sum::<f64>() / stats.cpu_count as f64;
+        let count = |state: ProcessState| processes.iter().filter(|p| p.state == state).count();
+        stats.tasks = processes.len();
+        stats.running = count(ProcessState::Running);
+        stats.disk_sleep = count(ProcessState::DiskSleep);
+        stats.zombies = count(ProcessState::Zombie);
+        stats.pressure = pressure;
+        Ok(stats)
+    }
+
+    pub fn memory_used_percent(&self) -> f64 {
+        if self.memory_total_kb == 0 {
+            return 0.0;
+        }
+        let used = self
+            .memory_total_kb
+            .saturating_sub(self.memory_available_kb);
+        used as f64 * 100.0 / self.memory_total_kb as f64
+    }
+}
+
+pub fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
+
+///Memory and swap from `/proc/meminfo`, the rest of the stats left at their defaults
+fn parse_meminfo(content: &str) -> Result<SystemStats> {
+    let mut stats = SystemStats::default();
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let kb = || {
+            value
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse::<u64>()
+                .with_context(|| format!("Bad meminfo line '{line}'"))
+        };
+        match key {
+            "MemTotal" => stats.memory_total_kb = kb()?,
+            "MemAvailable" => stats.memory_available_kb = kb()?,
+            "SwapTotal" => stats.swap_total_kb = kb()?,
+            "SwapFree" => stats.swap_free_kb = kb()?,
+            _ => {}
+        }
+    }
+    Ok(stats)
+}
+
+fn parse_loadavg(content: &str) -> Result<[f64; 3]> {
+    let mut fields = content.split_whitespace();
+    let mut next = || -> Result<f64> {
+        fields
+            .next()
+            .ok_or_else(|| anyhow!("Short loadavg '{content}'"))?
+            .parse::<f64>()
+            .with_context(|| format!("Bad loadavg '{content}'"))
+    };
+    Ok([next()?, next()?, next()?])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_system_files() -> Result<()> {
+        let stats = parse_meminfo(
+            "MemTotal:       16000000 kB\nMemFree:  1000 kB\nMemAvailable:    4000000 kB\nSwapTotal: 0 kB\nSwapFree: 0 kB\n",
+        )?;
+        assert_eq!(stats.memory_total_kb, 16_000_000);
+        assert_eq!(stats.memory_available_kb, 4_000_000);
+        assert_eq!(stats.memory_used_percent(), 75.0);
+        assert_eq!(
+            parse_loadavg("0.52 0.58 0.59 2/1019 12345\n")?,
+            [0.52, 0.58, 0.59]
+        );
+        assert!(parse_loadavg("0.52").is_err());
+        Ok(())
+    }
+}
@@ -1,28 +1,83 @@
 use std::time::Duration;
 
 use crossterm::event::{self, Event};
+use nix::sys::signal::Signal;
 use ratatui::{
     Frame, Terminal,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     prelude::Backend,
-    style::{Color, Style},
-    widgets::{Block, BorderType, Borders, Paragraph, Row, Table},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{
+        Block, BorderType, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Tabs, Wrap,
+    },
 };
 
-use crate::{app::App, output::format_memory};
+use crate::{
+    app::{App, Ghost, RowKey, TableRow},
+    app_args::AppArgs,
+    commands::{
+        Command, DETAIL_KEYS, GLOBAL_KEYS, OVERVIEW_KEYS, Palette, TABLE_KEYS, TABLE_MOTIONS,
+    },
+    detail::{DetailTab, ProcessDetail},
+    events::LifecycleKind,
+    limits::format_limit_value,
+    model::{GroupBy, ProcessGroup, ProcessInfo, ProcessState, SortBy, Target},
+    output::{
+        format_count_rate, format_cpu_time, format_duration, format_memory, format_rate,
+        format_start_time, sparkline,
+    },
+    overview::HostView,
+    pressure::{Pressure, PressureAverages, PressureMonitor},
+    prompt::Prompt,
+    protocol::Endpoint,
+    remote::Remote,
+    rules::RuleEngine,
+    sensors::{Sensor, SensorKind, SensorStatus},
+};
 use anyhow::Result;
 
-pub fn run_tui<B: Backend>(terminal: &mut Terminal<B>) -> Result<()> {
+///Index of the STATE column in the process table, before any optional columns
+const STATE_COLUMN: usize = 5;
+
+pub fn run_tui<B: Backend>(terminal: &mut Terminal<B>, args: &AppArgs) -> Result<()> {
     let mut app = App::new();
+    app.new_highlight = args.new_highlight;
+    app.ghost_duration = args.ghost;
+    match args.connect.as_slice() {
+        [] => {}
+        //a single agent has to be there, or there is nothing to show
+        [endpoint] => {
+            app.remotes.push(Remote::connect(
+                Endpoint::parse(endpoint),
+                args.token.clone(),
+            )?);
+            app.host_view = HostView::Host(0);
+        }
+        //with several, the ones that are up are worth watching while the others come back
+        endpoints => {
+            app.remotes = endpoints
+                .iter()
+                .map(|endpoint| Remote::watch(Endpoint::parse(endpoint), args.token.clone()))
+                .collect();
+            app.host_view = HostView::Overview;
+        }
+    }
+    if args.connect.is_empty() && args.netlink {
+        app.start_proc_connector();
+    }
+    if let Some(path) = &args.rules {
+        app.rules = Some(RuleEngine::load(path)?);
+    }
     loop {
         terminal.draw(|f| ui(f, &mut app))?;
         if app.should_quit {
             break;
         }
-        if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                app.handle_key(key.code);
-            }
+        if event::poll(Duration::from_millis(50))?
+            && let Event::Key(key) = event::read()?
+        {
+            app.handle_key_event(key);
         }
         app.update_processes()?;
     }
@@ -31,58 +86,1243 @@ pub fn run_tui<B: Backend>(terminal: &mut Terminal<B>) -> Result<()> {
 
 pub fn ui(f: &mut Frame, app: &mut App) {
     let size = f.area();
+    if app.host_view == HostView::Overview {
+        render_overview(f, app, size);
+        return;
+    }
 
-    let header = Row::new(vec![
-        "PID", "USER", "NAME", "CPU%", "MEM(KB)", "STATE", "COMMAND",
-    ])
+    let mut columns = vec![
+        ("PID", Some(SortBy::Pid)),
+        ("USER", None),
+        ("NAME", Some(SortBy::Name)),
+        ("CPU%", Some(SortBy::Cpu)),
+        ("MEM(KB)", Some(SortBy::Memory)),
+        ("STATE", None),
+        ("WCHAN", None),
+        ("CPU#", None),
+        ("AFFINITY", None),
+        ("START", Some(SortBy::Start)),
+        ("AGE", Some(SortBy::Age)),
+        ("TIME+", Some(SortBy::Time)),
+    ];
+    if app.show_activity {
+        columns.extend([
+            ("MINF/s", Some(SortBy::MinorFaults)),
+            ("MAJF/s", Some(SortBy::MajorFaults)),
+            ("CSW/s", Some(SortBy::VoluntarySwitches)),
+            ("ICSW/s", Some(SortBy::InvoluntarySwitches)),
+        ]);
+    }
+    if app.show_oom {
+        columns.extend([
+            ("OOM", Some(SortBy::OomScore)),
+            ("OOMADJ", Some(SortBy::OomScoreAdj)),
+        ]);
+    }
+    if app.show_security {
+        columns.push(("SECURITY", None));
+    }
+    if app.host_view == HostView::Merged {
+        columns.push(("HOST", None));
+    }
+    columns.push(("COMMAND", Some(SortBy::Command)));
+    //mark the sorted column, pointing the way values grow
+    let header = Row::new(columns.into_iter().map(|(title, sort)| {
+        match sort.filter(|sort| app.sort_by.as_ref() == Some(sort)) {
+            Some(_) if app.reverse_sort => format!("{title}▼"),
+            Some(_) => format!("{title}▲"),
+            None => title.to_string(),
+        }
+    }))
     .style(Style::default().fg(Color::Yellow))
     .height(1);
-    let widths = vec![
-        Constraint::Percentage(10),
-        Constraint::Percentage(10),
-        Constraint::Percentage(14),
-        Constraint::Percentage(10),
-        Constraint::Percentage(14),
-        Constraint::Percentage(14),
-        Constraint::Percentage(26),
+    //there are too many columns for percentages, give the numbers what they need
+    let mut widths = vec![
+        Constraint::Length(8),
+        Constraint::Length(9),
+        Constraint::Length(15),
+        Constraint::Length(6),
+        Constraint::Length(8),
+        Constraint::Length(9),
+        Constraint::Length(12),
+        Constraint::Length(4),
+        Constraint::Length(8),
+        Constraint::Length(6),
+        Constraint::Length(7),
+        Constraint::Length(8),
     ];
+    if app.show_activity {
+        widths.extend([
+            Constraint::Length(7),
+            Constraint::Length(7),
+            Constraint::Length(6),
+            Constraint::Length(7),
+        ]);
+    }
+    if app.show_oom {
+        widths.extend([Constraint::Length(5), Constraint::Length(7)]);
+    }
+    if app.show_security {
+        widths.push(Constraint::Length(24));
+    }
+    if app.host_view == HostView::Merged {
+        widths.push(Constraint::Length(16));
+    }
+    widths.push(Constraint::Min(10));
 
     let rows = app
-        .processes
-        .iter()
-        .map(|process| {
-            Row::new(vec![
-                process.pid.to_string(),
-                process.user.clone(),
-                process.name.clone(),
-                format!("{:1}", process.cpu_percent),
-                format_memory(process.memory_kb),
-                format!("{:?}", process.state),
-                process.command.clone(),
-            ])
-            .style(Style::default().fg(Color::LightCyan))
+        .table_rows()
+        .into_iter()
+        .map(|row| match row {
+            TableRow::Process(i) => process_row(app, &app.processes[i], ""),
+            TableRow::Member(i) => process_row(app, &app.processes[i], "  "),
+            TableRow::Group(i) => group_row(app, &app.groups[i]),
+            TableRow::Ghost(i) => ghost_row(app, &app.ghosts[i]),
         })
         .collect::<Vec<Row>>();
+    let mut table_block = Block::default()
+        .borders(Borders::all())
+        .border_type(BorderType::Double);
+    match app.host_view {
+        HostView::Host(i) => {
+            let remote = &app.remotes[i];
+            table_block = table_block.title(match remote.connected {
+                true => format!(" {} ({}) ", remote.hostname, remote.endpoint),
+                false => format!(" {} - reconnecting ", remote.hostname),
+            });
+        }
+        HostView::Merged => {
+            let up = app.remotes.iter().filter(|r| r.connected).count();
+            table_block =
+                table_block.title(format!(" All hosts, {up} of {} up ", app.remotes.len()));
+        }
+        HostView::Local | HostView::Overview => {}
+    }
+    if app.remotes.len() > 1 {
+        table_block = table_block.title(" [Q] hosts ");
+    }
+    if let Some((filter, _)) = &app.filter {
+        table_block = table_block.title(format!("Filter: {filter} - [/] change"));
+    }
+    if !app.tagged.is_empty() {
+        table_block = table_block.title(format!(" {} tagged - [U]ntag ", app.tagged.len()));
+    }
+    if let Some(followed) = &app.following {
+        table_block = table_block.title(
+            Line::from(format!(
+                " Following {} ({}) - [F] stop ",
+                followed.pid, followed.name
+            ))
+            .right_aligned(),
+        );
+    }
     let table = Table::new(rows, widths)
         .header(header)
-        .block(
-            Block::default()
-                .borders(Borders::all())
-                .border_type(BorderType::Double),
-        )
+        .block(table_block)
         .style(Style::default().fg(Color::Yellow))
         .row_highlight_style(Color::Cyan)
         .highlight_symbol(">>");
 
-    let menu = Paragraph::new("[Q]uit | [C]pu | [M]em | [P]ID | [N]ame | c[O]mmand")
-        .block(Block::default().borders(Borders::ALL).title("Menu"))
-        .style(Style::default().fg(Color::Yellow));
+    let menu = Paragraph::new(
+        "[Q]uit | [?] help | [Ctrl-P] commands | [C]pu [M]em [P]ID [N]ame c[O]mmand [<>] | [/] filter | [:] pid | [Enter] details | [A]ggr | [F]ollow | [Space] tag | [X] signal",
+    )
+    .block(status_block(app))
+    .style(Style::default().fg(Color::Yellow));
 
+    let mut constraints = vec![Constraint::Length(header_height(app))];
+    if app.show_network {
+        constraints.push(Constraint::Length(
+            (app.network.interfaces.len() as u16 + 3).min(12),
+        ));
+    }
+    if app.show_disks {
+        constraints.push(Constraint::Length(
+            (app.disks.disks.len() as u16 + 3).min(12),
+        ));
+    }
+    if app.show_sensors {
+        constraints.push(Constraint::Length(
+            (app.sensors.len() as u16 + 3).clamp(4, 12),
+        ));
+    }
+    constraints.push(Constraint::Min(5));
+    if app.show_events {
+        constraints.push(Constraint::Length(12));
+    }
+    constraints.push(Constraint::Length(3));
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(5), Constraint::Length(3)])
+        .constraints(constraints)
         .split(size);
 
-    f.render_stateful_widget(table, chunks[0], &mut app.table_state);
+    let mut next = 0;
+    let mut area = || {
+        next += 1;
+        chunks[next - 1]
+    };
+    render_header(f, app, area());
+    if app.show_network {
+        render_network(f, app, area());
+    }
+    if app.show_disks {
+        render_disks(f, app, area());
+    }
+    if app.show_sensors {
+        render_sensors(f, app, area());
+    }
+    match &app.detail {
+        Some(detail) => render_detail(f, detail, area()),
+        None => {
+            let table_area = area();
+            //less the borders and the header
+            app.page_size = table_area.height.saturating_sub(3).max(1) as usize;
+            f.render_stateful_widget(table, table_area, &mut app.table_state)
+        }
+    }
+    if app.show_events {
+        render_events(f, app, area());
+    }
+    match &app.prompt {
+        Some(prompt) if prompt.action.targets().len() > 1 => {
+            render_targets(f, app, prompt.action.host(), prompt.action.targets(), size);
+            render_prompt(f, prompt, chunks[chunks.len() - 1])
+        }
+        Some(prompt) => render_prompt(f, prompt, chunks[chunks.len() - 1]),
+        None => f.render_widget(menu, chunks[chunks.len() - 1]),
+    }
+    if app.show_help {
+        render_help(f, app, size);
+    }
+    if let Some(palette) = &app.palette {
+        render_palette(f, palette, size);
+    }
+}
+
+///Processes a bulk action is about to change, so Enter on the prompt is an informed one
+fn render_targets(f: &mut Frame, app: &App, host: Option<&str>, targets: &[Target], area: Rect) {
+    let rows = targets
+        .iter()
+        .map(|target| {
+            let process = app
+                .processes
+                .iter()
+                .find(|p| target.is(p) && p.host.as_deref() == host);
+            match process {
+                Some(process) => Row::new(vec![
+                    target.pid.to_string(),
+                    process.user.clone(),
+                    process.name.clone(),
+                    process.command.clone(),
+                ]),
+                //the pid may be taken by a new process by now, which is left alone
+                None => Row::new(vec![
+                    target.pid.to_string(),
+                    String::new(),
+                    "(exited)".to_string(),
+                    "skipped".to_string(),
+                ]),
+            }
+        })
+        .collect::<Vec<Row>>();
+    //above the prompt, which stays at the bottom
+    let height = (targets.len() as u16 + 2).min(area.height.saturating_sub(5));
+    let popup = centered(area, 100, height);
+    f.render_widget(Clear, popup);
+    f.render_widget(
+        Table::new(
+            rows,
+            [
+                Constraint::Length(8),
+                Constraint::Length(9),
+                Constraint::Length(16),
+                Constraint::Min(10),
+            ],
+        )
+        .style(Style::default().fg(Color::LightMagenta))
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "{} targets - [Enter] apply | [Esc] cancel",
+            targets.len()
+        ))),
+        popup,
+    );
+}
+
+///A `width` by `height` box in the middle of `area`, as much of it as fits
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+///Every key of the view underneath, over the top of it
+fn render_help(f: &mut Frame, app: &App, area: Rect) {
+    let mut entries: Vec<(String, String)> = GLOBAL_KEYS
+        .iter()
+        .map(|(keys, what)| (keys.to_string(), what.to_string()))
+        .collect();
+    let view_keys: Vec<(String, String)> = match app.detail {
+        Some(_) => DETAIL_KEYS
+            .iter()
+            .map(|(keys, what)| (keys.to_string(), what.to_string()))
+            .collect(),
+        None if app.host_view == HostView::Overview => OVERVIEW_KEYS
+            .iter()
+            .map(|(keys, what)| (keys.to_string(), what.to_string()))
+            .collect(),
+        None => {
+            let mut commands: Vec<Command> = Vec::new();
+            for (_, command) in TABLE_KEYS.iter() {
+                if !commands.contains(command) && !matches!(command, Command::Quit | Command::Help)
+                {
+                    commands.push(command.clone());
+                }
+            }
+            TABLE_MOTIONS
+                .iter()
+                .map(|(keys, what)| (keys.to_string(), what.to_string()))
+                .chain(commands.iter().map(|c| (c.keys(), c.label())))
+                .collect()
+        }
+    };
+    entries.push((String::new(), String::new()));
+    entries.extend(view_keys);
+    let lines: Vec<Line> = entries
+        .into_iter()
+        .map(|(keys, what)| {
+            Line::from(vec![
+                Span::styled(format!("{keys:>12}  "), Style::default().fg(Color::Cyan)),
+                Span::raw(what),
+            ])
+        })
+        .collect();
+    let title = match &app.detail {
+        Some(_) => "Keys of the detail view - [j/k] scroll | any other key closes",
+        None if app.host_view == HostView::Overview => {
+            "Keys of the host overview - [j/k] scroll | any other key closes"
+        }
+        None => "Keys of the process table - [j/k] scroll | any other key closes",
+    };
+    let popup = centered(area, 72, lines.len() as u16 + 2);
+    f.render_widget(Clear, popup);
+    f.render_widget(
+        Paragraph::new(lines)
+            .scroll((app.help_scroll, 0))
+            .style(Style::default().fg(Color::Yellow))
+            .block(Block::default().borders(Borders::ALL).title(title)),
+        popup,
+    );
+}
+
+///Fuzzy searchable list of every command, with the key that runs it where there is one
+fn render_palette(f: &mut Frame, palette: &Palette, area: Rect) {
+    let matches = palette.matches();
+    let popup = centered(area, 64, 18);
+    f.render_widget(Clear, popup);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Commands - [↑↓] pick | [Enter] run | [Esc] close");
+    let inner = block.inner(popup);
+    f.render_widget(block, popup);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(inner);
+    f.render_widget(
+        Paragraph::new(format!("> {}█", palette.query)).style(Style::default().fg(Color::White)),
+        chunks[0],
+    );
+    let rows = matches
+        .iter()
+        .map(|command| Row::new(vec![command.label(), command.keys()]))
+        .collect::<Vec<Row>>();
+    let mut state = TableState::default();
+    state.select((!matches.is_empty()).then_some(palette.selected));
+    f.render_stateful_widget(
+        Table::new(rows, [Constraint::Min(20), Constraint::Length(8)])
+            .style(Style::default().fg(Color::Yellow))
+            .row_highlight_style(Color::Cyan)
+            .highlight_symbol(">>"),
+        chunks[1],
+        &mut state,
+    );
+}
+
+///Input line in place of the menu
+fn render_prompt(f: &mut Frame, prompt: &Prompt, area: Rect) {
+    let line = Line::from(vec![
+        Span::styled(
+            format!("{}: ", prompt.label),
+            Style::default().fg(Color::Yellow),
+        ),
+        Span::styled(
+            format!("{}█", prompt.input),
+            Style::default().fg(Color::White),
+        ),
+    ]);
+    f.render_widget(
+        Paragraph::new(line).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Input - [Enter] apply | [Esc] cancel"),
+        ),
+        area,
+    );
+}
+
+///Tabs with everything we know about one process, in place of the process table
+fn render_detail(f: &mut Frame, detail: &ProcessDetail, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::all())
+        .border_type(BorderType::Double)
+        .title(format!(
+            "PID {} {} - [Tab] switch | [R]eload | {}[Esc] back",
+            detail.target.pid,
+            detail.name,
+            if detail.tab == DetailTab::Limits {
+                "[E]dit limit | "
+            } else {
+                ""
+            }
+        ));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(inner);
+    let tabs = Tabs::new(DetailTab::ALL.iter().map(|t| t.title()))
+        .select(DetailTab::ALL.iter().position(|t| *t == detail.tab))
+        .style(Style::default().fg(Color::Yellow))
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+    f.render_widget(tabs, chunks[0]);
+
+    match detail.tab {
+        DetailTab::Mappings | DetailTab::Regions => render_memory_map(f, detail, chunks[1]),
+        DetailTab::Kernel => render_kernel_stacks(f, detail, chunks[1]),
+        DetailTab::Limits => render_limits(f, detail, chunks[1]),
+        DetailTab::Security => render_security(f, detail, chunks[1]),
+    }
+}
+
+///Credentials and privileges, capability lists wrap as they can be long
+fn render_security(f: &mut Frame, detail: &ProcessDetail, area: Rect) {
+    let lines = detail
+        .security_lines()
+        .into_iter()
+        .skip(detail.scroll)
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(format!("{label:<15}"), Style::default().fg(Color::Yellow)),
+                Span::styled(value, Style::default().fg(Color::LightCyan)),
+            ])
+        })
+        .collect::<Vec<Line>>();
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), area);
+}
+
+///Soft and hard limits with current usage, the selected one can be edited
+fn render_limits(f: &mut Frame, detail: &ProcessDetail, area: Rect) {
+    let limits = match &detail.limits {
+        Ok(limits) => limits,
+        Err(e) => {
+            f.render_widget(
+                Paragraph::new(format!("Cannot read limits: {e}"))
+                    .style(Style::default().fg(Color::LightRed)),
+                area,
+            );
+            return;
+        }
+    };
+    let rows = limits.iter().map(|limit| {
+        let used = detail.limit_usage.for_limit(&limit.name);
+        //close to the soft limit is where "too many open files" comes from
+        let percent = used
+            .zip(limit.soft)
+            .filter(|(_, soft)| *soft > 0)
+            .map(|(used, soft)| used as f64 * 100.0 / soft as f64);
+        let color = match percent {
+            Some(p) if p >= 90.0 => Color::LightRed,
+            Some(p) if p >= 70.0 => Color::LightYellow,
+            _ => Color::LightCyan,
+        };
+        Row::new(vec![
+            limit.name.clone(),
+            format_limit_value(limit.soft),
+            format_limit_value(limit.hard),
+            limit.units.clone(),
+            used.map(|u| u.to_string()).unwrap_or_default(),
+            percent.map(|p| format!("{p:.1}%")).unwrap_or_default(),
+        ])
+        .style(Style::default().fg(color))
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(24),
+            Constraint::Length(22),
+            Constraint::Length(22),
+            Constraint::Length(10),
+            Constraint::Length(22),
+            Constraint::Length(8),
+        ],
+    )
+    .header(
+        Row::new(vec!["LIMIT", "SOFT", "HARD", "UNITS", "USED", "OF SOFT"])
+            .style(Style::default().fg(Color::Yellow)),
+    )
+    .row_highlight_style(Color::Cyan)
+    .highlight_symbol(">>");
+    let mut state = TableState::default().with_selected(Some(detail.scroll));
+    f.render_stateful_widget(table, area, &mut state);
+}
+
+///Per thread wait channel and kernel stack
+fn render_kernel_stacks(f: &mut Frame, detail: &ProcessDetail, area: Rect) {
+    let lines = detail
+        .kernel_lines()
+        .into_iter()
+        .skip(detail.scroll)
+        .map(|(heading, text)| {
+            let color = if heading {
+                Color::Yellow
+            } else {
+                Color::LightCyan
+            };
+            Line::styled(text, Style::default().fg(color))
+        })
+        .collect::<Vec<Line>>();
+    f.render_widget(Paragraph::new(lines), area);
+}
+
+///Memory map of the detail process, per mapping or per region
+fn render_memory_map(f: &mut Frame, detail: &ProcessDetail, area: Rect) {
+    let regions = match &detail.regions {
+        Ok(regions) => regions,
+        Err(e) => {
+            f.render_widget(
+                Paragraph::new(format!("Cannot read memory map: {e}"))
+                    .style(Style::default().fg(Color::LightRed)),
+                area,
+            );
+            return;
+        }
+    };
+    let usage = |kb: Option<u64>| kb.map(format_memory).unwrap_or_else(|| "-".to_string());
+    let (header, widths, rows): (Vec<&str>, Vec<Constraint>, Vec<Row>) = match detail.tab {
+        DetailTab::Regions => (
+            vec![
+                "ADDRESS", "PERMS", "OFFSET", "SIZE", "RSS", "PSS", "MAPPING",
+            ],
+            vec![
+                Constraint::Length(34),
+                Constraint::Length(6),
+                Constraint::Length(10),
+                Constraint::Length(9),
+                Constraint::Length(9),
+                Constraint::Length(9),
+                Constraint::Min(10),
+            ],
+            regions
+                .iter()
+                .skip(detail.scroll)
+                .map(|region| {
+                    Row::new(vec![
+                        format!("{:016x}-{:016x}", region.start, region.end),
+                        region.perms.clone(),
+                        format!("{:x}", region.offset),
+                        format_memory(region.size_kb()),
+                        usage(region.rss_kb),
+                        usage(region.pss_kb),
+                        region.mapping_name().to_string(),
+                    ])
+                })
+                .collect(),
+        ),
+        _ => (
+            vec!["MAPPING", "REGIONS", "SIZE", "RSS", "PSS"],
+            vec![
+                Constraint::Min(20),
+                Constraint::Length(8),
+                Constraint::Length(9),
+                Constraint::Length(9),
+                Constraint::Length(9),
+            ],
+            detail
+                .mappings
+                .iter()
+                .skip(detail.scroll)
+                .map(|mapping| {
+                    let known = detail.has_usage();
+                    Row::new(vec![
+                        mapping.name.clone(),
+                        mapping.regions.to_string(),
+                        format_memory(mapping.size_kb),
+                        usage(known.then_some(mapping.rss_kb)),
+                        usage(known.then_some(mapping.pss_kb)),
+                    ])
+                })
+                .collect(),
+        ),
+    };
+    let table = Table::new(rows, widths)
+        .header(Row::new(header).style(Style::default().fg(Color::Yellow)))
+        .style(Style::default().fg(Color::LightCyan));
+    f.render_widget(table, area);
+}
+
+///A row per agent with its numbers and busiest process, and a last one for all of them
+fn render_overview(f: &mut Frame, app: &mut App, area: Rect) {
+    let summaries = app.host_summaries();
+    let load_style = |percent: f64| match percent {
+        p if p >= 90.0 => Style::default().fg(Color::LightRed),
+        p if p >= 70.0 => Style::default().fg(Color::LightYellow),
+        _ => Style::default(),
+    };
+    let mut rows: Vec<Row> = summaries
+        .iter()
+        .map(|summary| {
+            let status = Cell::from(summary.status.clone()).style(match summary.connected {
+                true => Style::default().fg(Color::LightGreen),
+                false => Style::default().fg(Color::LightRed),
+            });
+            //no numbers yet, rather than zeros that look like an idle host
+            if summary.tasks == 0 && summary.memory_total_kb == 0 {
+                return Row::new(vec![Cell::from(summary.hostname.clone()), status]);
+            }
+            let top = summary
+                .top
+                .as_ref()
+                .map(|p| format!("{} {} {:.1}%", p.pid, p.name, p.cpu_percent))
+                .unwrap_or_default();
+            Row::new(vec![
+                Cell::from(summary.hostname.clone()),
+                status,
+                Cell::from(format!("{:.1}", summary.cpu_percent))
+                    .style(load_style(summary.cpu_percent)),
+                Cell::from(summary.cpu_count.to_string()),
+                Cell::from(format!("{:.1}", summary.memory_percent))
+                    .style(load_style(summary.memory_percent)),
+                Cell::from(format_memory(summary.memory_total_kb)),
+                Cell::from(
+                    summary
+                        .load_average
+                        .iter()
+                        .map(|load| format!("{load:.2}"))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                ),
+                Cell::from(summary.tasks.to_string()),
+                Cell::from(top),
+            ])
+        })
+        .collect();
+    rows.push(
+        Row::new(vec!["All hosts (merged)".to_string()])
+            .style(Style::default().fg(Color::LightGreen)),
+    );
+    let header = Row::new(vec![
+        "HOST",
+        "STATUS",
+        "CPU%",
+        "CPUS",
+        "MEM%",
+        "MEMORY",
+        "LOAD",
+        "TASKS",
+        "TOP PROCESS",
+    ])
+    .style(Style::default().fg(Color::Yellow));
+    let up = summaries.iter().filter(|s| s.connected).count();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(24),
+            Constraint::Length(30),
+            Constraint::Length(6),
+            Constraint::Length(5),
+            Constraint::Length(6),
+            Constraint::Length(9),
+            Constraint::Length(16),
+            Constraint::Length(6),
+            Constraint::Min(20),
+        ],
+    )
+    .header(header)
+    .block(
+        Block::default()
+            .borders(Borders::all())
+            .border_type(BorderType::Double)
+            .title(format!(
+                " Hosts, {up} of {} up - sorted by {} ",
+                summaries.len(),
+                app.host_sort.title()
+            )),
+    )
+    .style(Style::default().fg(Color::Yellow))
+    .row_highlight_style(Color::Cyan)
+    .highlight_symbol(">>");
+    let menu = Paragraph::new(
+        "[Q]uit | [?] help | [Enter] processes of the host | sort by [C]pu [M]em [L]oad [N]ame",
+    )
+    .block(status_block(app))
+    .style(Style::default().fg(Color::Yellow));
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(5), Constraint::Length(3)])
+        .split(area);
+    f.render_stateful_widget(table, chunks[0], &mut app.host_state);
     f.render_widget(menu, chunks[1]);
+    if app.show_help {
+        render_help(f, app, area);
+    }
+}
+
+///Border around the menu, carrying the current notice if there is one
+fn status_block(app: &App) -> Block<'static> {
+    let block = Block::default().borders(Borders::ALL).title("Menu");
+    match app.current_notice() {
+        Some(notice) => block.title(
+            Line::from(format!(" {notice} "))
+                .right_aligned()
+                .style(Style::default().fg(Color::LightRed)),
+        ),
+        None => block,
+    }
+}
+
+///One line summary of the process states, with the busiest disk next to the D-state count
+fn render_header(f: &mut Frame, app: &App, area: Rect) {
+    let count = |state: ProcessState| app.count_in_state(&state);
+    let disk_sleep = count(ProcessState::DiskSleep);
+    let alarm = |active: bool| {
+        if active {
+            Style::default().fg(Color::LightRed)
+        } else {
+            Style::default().fg(Color::Yellow)
+        }
+    };
+    let mut spans = vec![
+        Span::raw(format!(
+            "Tasks: {} total, {} running, {} sleeping, ",
+            app.processes.len(),
+            count(ProcessState::Running),
+            count(ProcessState::Sleeping)
+        )),
+        Span::styled(format!("{disk_sleep} disk sleep"), alarm(disk_sleep > 0)),
+        Span::raw(format!(", {} zombie", count(ProcessState::Zombie))),
+    ];
+    if let Some(disk) = app.disks.busiest() {
+        spans.push(Span::styled(
+            format!(
+                " | {} {:.0}% util {:.1}ms",
+                disk.name, disk.utilization_percent, disk.await_ms
+            ),
+            alarm(disk_sleep > 0 || disk.utilization_percent >= 90.0),
+        ));
+    }
+    let mut lines = vec![Line::from(spans)];
+    if let Some(pressure) = &app.pressure.current {
+        let mut spans = vec![Span::raw("PSI ")];
+        for (i, (name, resource)) in PressureMonitor::RESOURCES.iter().zip(pressure).enumerate() {
+            spans.extend(pressure_spans(name, resource));
+            spans.push(Span::raw(format!(
+                " {} ",
+                sparkline(&app.pressure.history[i], 12)
+            )));
+        }
+        lines.push(Line::from(spans));
+    }
+    if let Some((cgroup, pressure)) = &app.selected_cgroup_pressure {
+        let mut spans = vec![Span::raw(format!("cgroup {cgroup}: "))];
+        for (name, resource) in PressureMonitor::RESOURCES.iter().zip(pressure) {
+            spans.extend(pressure_spans(name, resource));
+            spans.push(Span::raw(" "));
+        }
+        lines.push(Line::from(spans));
+    }
+    f.render_widget(
+        Paragraph::new(lines).style(Style::default().fg(Color::Yellow)),
+        area,
+    );
+}
+
+///Tasks line, plus system and selected cgroup pressure when the kernel has PSI
+fn header_height(app: &App) -> u16 {
+    1 + app.pressure.current.is_some() as u16 + app.selected_cgroup_pressure.is_some() as u16
+}
+
+///"cpu 3.2 2.7 2.3 full 0.0 0.0 0.0", colored by how stalled we are
+fn pressure_spans(name: &str, pressure: &Pressure) -> Vec<Span<'static>> {
+    let averages = |a: &PressureAverages| format!("{:.1} {:.1} {:.1}", a.avg10, a.avg60, a.avg300);
+    let severity = |a: &PressureAverages| {
+        Style::default().fg(if a.avg10 >= 40.0 {
+            Color::LightRed
+        } else if a.avg10 >= 10.0 {
+            Color::LightYellow
+        } else {
+            Color::LightGreen
+        })
+    };
+    let mut spans = vec![Span::styled(
+        format!("{name} {}", averages(&pressure.some)),
+        severity(&pressure.some),
+    )];
+    if let Some(full) = &pressure.full {
+        spans.push(Span::styled(
+            format!(" full {}", averages(full)),
+            severity(full),
+        ));
+    }
+    spans
+}
+
+///Per device throughput, IOPS, request latency and utilization
+fn render_disks(f: &mut Frame, app: &App, area: Rect) {
+    const HISTORY_WIDTH: usize = 30;
+    let header = Row::new(vec![
+        "DEVICE",
+        "READ",
+        "WRITE",
+        "R/s",
+        "W/s",
+        "AWAIT",
+        "UTIL%",
+        "UTIL HISTORY",
+    ])
+    .style(Style::default().fg(Color::Yellow));
+    let rows = app
+        .disks
+        .disks
+        .iter()
+        .map(|disk| {
+            Row::new(vec![
+                disk.name.clone(),
+                format_rate(disk.read_bytes_per_sec),
+                format_rate(disk.write_bytes_per_sec),
+                format!("{:.0}", disk.reads_per_sec),
+                format!("{:.0}", disk.writes_per_sec),
+                format!("{:.1}ms", disk.await_ms),
+                format!("{:.0}", disk.utilization_percent),
+                sparkline(&disk.utilization_history, HISTORY_WIDTH),
+            ])
+            .style(Style::default().fg(if disk.utilization_percent >= 90.0 {
+                Color::LightRed
+            } else {
+                Color::LightCyan
+            }))
+        })
+        .collect::<Vec<Row>>();
+    let widths = vec![
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(7),
+        Constraint::Length(7),
+        Constraint::Length(9),
+        Constraint::Length(6),
+        Constraint::Length(HISTORY_WIDTH as u16),
+    ];
+    let title = if app.disks.show_all {
+        "Disks (all devices)"
+    } else {
+        "Disks"
+    };
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(table, area);
+}
+
+///Temperatures, fans and voltages, colored as they approach their limits
+fn render_sensors(f: &mut Frame, app: &App, area: Rect) {
+    let header = Row::new(vec!["CHIP", "SENSOR", "VALUE", "HIGH", "CRIT"])
+        .style(Style::default().fg(Color::Yellow));
+    let reading = |sensor: &Sensor, value: Option<f64>| match (value, sensor.kind) {
+        (Some(v), SensorKind::Voltage) => format!("{v:.2}{}", sensor.kind.unit()),
+        (Some(v), _) => format!("{v:.0}{}", sensor.kind.unit()),
+        (None, _) => String::new(),
+    };
+    let rows = app
+        .sensors
+        .iter()
+        .map(|sensor| {
+            let color = match sensor.status() {
+                SensorStatus::Normal => Color::LightCyan,
+                SensorStatus::High => Color::LightYellow,
+                SensorStatus::Critical => Color::LightRed,
+            };
+            Row::new(vec![
+                sensor.chip.clone(),
+                sensor.label.clone(),
+                reading(sensor, Some(sensor.value)),
+                reading(sensor, sensor.high.or(sensor.low)),
+                reading(sensor, sensor.critical),
+            ])
+            .style(Style::default().fg(color))
+        })
+        .collect::<Vec<Row>>();
+    let widths = vec![
+        Constraint::Length(14),
+        Constraint::Length(20),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(10),
+    ];
+    let title = if app.sensors.is_empty() {
+        "Sensors (none found)"
+    } else {
+        "Sensors"
+    };
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(table, area);
+}
+
+///Per interface throughput, with sparklines of recent receive and transmit rates
+fn render_network(f: &mut Frame, app: &App, area: Rect) {
+    const HISTORY_WIDTH: usize = 30;
+    let header = Row::new(vec![
+        "IFACE",
+        "RX",
+        "TX",
+        "RX PKT/s",
+        "TX PKT/s",
+        "ERR/s",
+        "DROP/s",
+        "RX HISTORY",
+        "TX HISTORY",
+    ])
+    .style(Style::default().fg(Color::Yellow));
+    let rows = app
+        .network
+        .interfaces
+        .iter()
+        .map(|iface| {
+            let troubled = iface.rx_errors_per_sec + iface.tx_errors_per_sec > 0.0
+                || iface.rx_drops_per_sec + iface.tx_drops_per_sec > 0.0;
+            Row::new(vec![
+                iface.name.clone(),
+                format_rate(iface.rx_bytes_per_sec),
+                format_rate(iface.tx_bytes_per_sec),
+                format!("{:.0}", iface.rx_packets_per_sec),
+                format!("{:.0}", iface.tx_packets_per_sec),
+                format!("{:.0}", iface.rx_errors_per_sec + iface.tx_errors_per_sec),
+                format!("{:.0}", iface.rx_drops_per_sec + iface.tx_drops_per_sec),
+                sparkline(&iface.rx_history, HISTORY_WIDTH),
+                sparkline(&iface.tx_history, HISTORY_WIDTH),
+            ])
+            .style(Style::default().fg(if troubled {
+                Color::LightRed
+            } else {
+                Color::LightCyan
+            }))
+        })
+        .collect::<Vec<Row>>();
+    let widths = vec![
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(6),
+        Constraint::Length(7),
+        Constraint::Length(HISTORY_WIDTH as u16),
+        Constraint::Length(HISTORY_WIDTH as u16),
+    ];
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title("Network"));
+    f.render_widget(table, area);
+}
+
+///Pane listing processes that started and exited, newest first
+fn render_events(f: &mut Frame, app: &App, area: Rect) {
+    let header = Row::new(vec![
+        "TIME", "EVENT", "PID", "PARENT", "LIFETIME", "CPU%", "MEM", "EXIT", "COMMAND",
+    ])
+    .style(Style::default().fg(Color::Yellow));
+    let widths = vec![
+        Constraint::Length(8),
+        Constraint::Length(7),
+        Constraint::Length(8),
+        Constraint::Length(20),
+        Constraint::Length(9),
+        Constraint::Length(6),
+        Constraint::Length(8),
+        Constraint::Length(8),
+        Constraint::Min(10),
+    ];
+    let rows = app
+        .event_log
+        .visible()
+        .take(area.height as usize)
+        .map(|event| {
+            let (label, color) = match event.kind {
+                LifecycleKind::Started => ("start", Color::LightGreen),
+                LifecycleKind::Exec => ("exec", Color::LightYellow),
+                LifecycleKind::Exited => ("exit", Color::LightRed),
+            };
+            let exited = event.kind == LifecycleKind::Exited;
+            Row::new(vec![
+                event.timestamp.format("%H:%M:%S").to_string(),
+                label.to_string(),
+                event.pid.to_string(),
+                format!("{} {}", event.ppid, event.parent),
+                event
+                    .lifetime_secs
+                    .map(|secs| format_duration(secs as u64))
+                    .unwrap_or_default(),
+                if exited {
+                    format!("{:.1}", event.cpu_percent)
+                } else {
+                    String::new()
+                },
+                if exited {
+                    format_memory(event.memory_kb)
+                } else {
+                    String::new()
+                },
+                match (event.exit_code, event.exit_signal) {
+                    (Some(code), _) => code.to_string(),
+                    (_, Some(signal)) => Signal::try_from(signal)
+                        .map(|s| s.as_str().to_string())
+                        .unwrap_or_else(|_| format!("sig {signal}")),
+                    _ => String::new(),
+                },
+                if event.command.is_empty() {
+                    event.name.clone()
+                } else {
+                    event.command.clone()
+                },
+            ])
+            .style(Style::default().fg(color))
+        })
+        .collect::<Vec<Row>>();
+    let title = if app.event_log.scroll > 0 {
+        format!(
+            "Events ({} of {})",
+            app.event_log.events.len() - app.event_log.scroll,
+            app.event_log.events.len()
+        )
+    } else {
+        format!("Events ({})", app.event_log.events.len())
+    };
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(title));
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+        .split(area);
+    f.render_widget(table, chunks[0]);
+    render_command_cpu(f, app, chunks[1]);
+}
+
+///Cumulative cpu of exited processes per command, heaviest first
+fn render_command_cpu(f: &mut Frame, app: &App, area: Rect) {
+    let mut usage: Vec<_> = app.command_cpu.iter().collect();
+    usage.sort_by(|a, b| {
+        b.1.cpu_secs
+            .partial_cmp(&a.1.cpu_secs)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let rows = usage
+        .into_iter()
+        .take(area.height as usize)
+        .map(|(command, usage)| {
+            Row::new(vec![
+                command.clone(),
+                usage.runs.to_string(),
+                format!("{:.2}s", usage.cpu_secs),
+            ])
+        })
+        .collect::<Vec<Row>>();
+    let source = if app.proc_connector.is_some() {
+        "netlink"
+    } else {
+        "polled"
+    };
+    let widths = vec![
+        Constraint::Min(10),
+        Constraint::Length(6),
+        Constraint::Length(10),
+    ];
+    let table = Table::new(rows, widths)
+        .header(Row::new(vec!["COMMAND", "RUNS", "CPU"]).style(Style::default().fg(Color::Yellow)))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Exited CPU ({source})")),
+        );
+    f.render_widget(table, area);
+}
+
+///A single process, `indent` is used to nest group members under their group
+fn process_row<'a>(app: &App, process: &ProcessInfo, indent: &str) -> Row<'a> {
+    let alerting = app
+        .rules
+        .as_ref()
+        .is_some_and(|rules| rules.highlighted.contains(&RowKey::of(process)));
+    let color = if alerting {
+        Color::LightRed
+    } else if app.is_tagged(process) {
+        Color::LightMagenta
+    } else if app.is_new(process) {
+        Color::LightYellow
+    } else {
+        Color::LightCyan
+    };
+    let mut style = Style::default().fg(color);
+    if app
+        .following
+        .as_ref()
+        .is_some_and(|f| f.pid == process.pid && f.start_time == process.start_time)
+    {
+        style = style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    }
+    Row::new(process_cells(app, process, indent)).style(style)
+}
+
+///A process that exited, dimmed until it is dropped
+fn ghost_row<'a>(app: &App, ghost: &Ghost) -> Row<'a> {
+    let mut cells = process_cells(app, &ghost.process, "");
+    cells[STATE_COLUMN] = "Exited".to_string();
+    Row::new(cells).style(
+        Style::default()
+            .fg(Color::DarkGray)
+            .add_modifier(Modifier::DIM),
+    )
+}
+
+fn process_cells(app: &App, process: &ProcessInfo, indent: &str) -> Vec<String> {
+    let tagged = app.is_tagged(process);
+    let rates = &process.activity_rates;
+    let mut cells = vec![
+        format!("{indent}{}{}", if tagged { "*" } else { "" }, process.pid),
+        process.user.clone(),
+        process.name.clone(),
+        format!("{:1}", process.cpu_percent),
+        format_memory(process.memory_kb),
+        format!("{:?}", process.state),
+        process.wchan.clone(),
+        process
+            .last_cpu
+            .map(|cpu| cpu.to_string())
+            .unwrap_or_default(),
+        process.cpus_allowed.clone(),
+        format_start_time(&process.started_at()),
+        format_duration(process.age_seconds()),
+        format_cpu_time(process.cpu_time_secs()),
+    ];
+    if app.show_activity {
+        cells.extend([
+            format_count_rate(rates.minor_faults),
+            format_count_rate(rates.major_faults),
+            format_count_rate(rates.voluntary_switches),
+            format_count_rate(rates.involuntary_switches),
+        ]);
+    }
+    if app.show_oom {
+        cells.extend([
+            process.oom_score.to_string(),
+            process.oom_score_adj.to_string(),
+        ]);
+    }
+    if app.show_security {
+        cells.push(process.security.flags());
+    }
+    if app.host_view == HostView::Merged {
+        cells.push(process.host.clone().unwrap_or_default());
+    }
+    cells.push(process.command.clone());
+    cells
+}
+
+///Summary row for a group of processes
+fn group_row<'a>(app: &App, group: &ProcessGroup) -> Row<'a> {
+    let marker = if app.expanded_groups.contains(&group.key) {
+        "[-]"
+    } else {
+        "[+]"
+    };
+    let mut users: Vec<&str> = group
+        .members
+        .iter()
+        .map(|&i| app.processes[i].user.as_str())
+        .collect();
+    users.sort();
+    users.dedup();
+    let user = match users.as_slice() {
+        [user] => user.to_string(),
+        _ => format!("{} users", users.len()),
+    };
+    //executables are full paths, commands can be kernel thread names like "kworker/0:1"
+    let (label, name) = match app.group_by {
+        Some(GroupBy::Executable) => ("exe", group.key.rsplit('/').next().unwrap_or(&group.key)),
+        _ => ("cmd", group.key.as_str()),
+    };
+    let mut cells = vec![
+        format!("{marker} x{}", group.count()),
+        user,
+        name.to_string(),
+        format!("{:.1}", group.cpu_percent),
+        format_memory(group.memory_kb),
+        format!("{} procs", group.count()),
+        String::new(),
+        String::new(),
+        String::new(),
+        String::new(),
+        String::new(),
+        format_cpu_time(
+            group
+                .members
+                .iter()
+                .map(|&i| app.processes[i].cpu_time_secs())
+                .sum(),
+        ),
+    ];
+    if app.show_activity {
+        cells.extend([
+            format_count_rate(group.activity_rates.minor_faults),
+            format_count_rate(group.activity_rates.major_faults),
+            format_count_rate(group.activity_rates.voluntary_switches),
+            format_count_rate(group.activity_rates.involuntary_switches),
+        ]);
+    }
+    if app.show_oom {
+        cells.extend([
+            group
+                .max_member(&app.processes, |p| p.oom_score)
+                .to_string(),
+            group
+                .max_member(&app.processes, |p| p.oom_score_adj)
+                .to_string(),
+        ]);
+    }
+    if app.show_security {
+        cells.push(String::new());
+    }
+    if app.host_view == HostView::Merged {
+        let mut hosts: Vec<&str> = group
+            .members
+            .iter()
+            .filter_map(|&i| app.processes[i].host.as_deref())
+            .collect();
+        hosts.sort();
+        hosts.dedup();
+        cells.push(match hosts.as_slice() {
+            [host] => host.to_string(),
+            _ => format!("{} hosts", hosts.len()),
+        });
+    }
+    cells.push(format!("{label}: {}", group.key));
+    Row::new(cells).style(Style::default().fg(Color::LightGreen))
 }